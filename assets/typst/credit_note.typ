@@ -0,0 +1,39 @@
+// The built-in Typst credit note template.
+
+#import "pdoc.typ": *
+
+#let credit-note = data.credit_note
+#let invoice = data.invoice
+#let currency = invoice.currency
+#let number = str(credit-note.number)
+
+#show: setup.with("Credit Note", number)
+
+#header("Credit Note", credit-note.date, number)
+
+#parties((
+  ("Invoice", "#" + str(invoice.number) + " of " + long-date(invoice.date)),
+  ("Amount Credited", data.totals.total + " " + currency),
+  ("Balance Due", data.balance + " " + currency),
+  ("Project", data.project.name),
+  ("Description", data.project.description),
+))
+
+#v(1fr)
+
+#items-table(invoice, data.formatted)
+
+#v(1fr)
+
+This credit note reduces the amount owed on invoice \##invoice.number of #long-date(invoice.date) by #data.totals.total #currency.
+#if data.refund_due [
+  The overpaid amount of #data.refund #currency will be refunded.
+] else if data.paid_in_full [
+  Nothing further is owed on this invoice.
+] else [
+  The remaining balance of #data.balance #currency is due #long-date(invoice.due_date).
+]
+
+#v(1fr)
+
+#merchant()
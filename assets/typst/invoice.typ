@@ -0,0 +1,31 @@
+// The built-in Typst invoice template.
+
+#import "pdoc.typ": *
+
+#let invoice = data.invoice
+#let number = str(invoice.number)
+
+#show: setup.with("Invoice", number)
+
+#header("Invoice", invoice.date, number)
+
+#parties((
+  ("Due", long-date(invoice.due_date)),
+  ("Project", data.project.name),
+  ("Description", data.project.description),
+))
+
+#v(1fr)
+
+#items-table(invoice, data.formatted)
+
+#v(1fr)
+
+#let conditions = invoice.at("conditions", default: none)
+#if conditions != none {
+  conditions
+}
+
+#v(1fr)
+
+#merchant()
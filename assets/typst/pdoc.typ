@@ -0,0 +1,151 @@
+// Helpers and layout shared by the built-in Typst templates.
+//
+// pdoc writes the document's data to data.json next to the templates,
+// with the same fields as user-supplied LaTeX templates receive.
+
+#let data = json("data.json")
+
+#let branding = data.me.at("branding", default: (:))
+
+#let accent = {
+  let color = branding.at("accent_color", default: none)
+  if color == none { black } else { rgb("#" + color) }
+}
+
+// The filename pdoc copies a branding image to, e.g. logo.png, or none.
+#let branding-image(name) = {
+  let path = branding.at(name, default: none)
+  if path == none { none } else { name + "." + lower(path.split(".").last()) }
+}
+
+// Format a YYYY-MM-DD date, e.g. January 7, 2023.
+#let long-date(date) = {
+  let (year, month, day) = date.split("-").map(int)
+  datetime(year: year, month: month, day: day).display("[month repr:long] [day padding:none], [year]")
+}
+
+#let address-lines(address) = {
+  let lines = (
+    address.addr1,
+    address.at("addr2", default: none),
+    address.at("addr3", default: none),
+    address.city + ", " + address.state + " " + address.zip,
+  )
+  lines.filter(line => line != none).join(linebreak())
+}
+
+#let setup(title, number, body) = {
+  set document(title: title + " " + number, author: data.me.name)
+  set page(paper: "us-letter", margin: 2cm)
+  set text(size: 10pt)
+  body
+}
+
+// The title, date and number, with the logo on the right.
+#let header(title, date, number) = {
+  let logo = branding-image("logo")
+  grid(
+    columns: (1fr, auto),
+    align: bottom,
+    text(fill: accent)[
+      #text(size: 28pt, weight: "light", tracking: 0.1em, upper(title)) \
+      #long-date(date)
+    ],
+    if logo != none { image(logo, width: 6cm, height: 2.5cm, fit: "contain") } else { [] },
+  )
+  v(1em)
+  text(fill: accent, size: 18pt)[\##number]
+  v(1em)
+}
+
+// Labelled details of the document next to the client's address.
+#let parties(details) = {
+  let client = data.client
+  grid(
+    columns: (38%, 1fr),
+    column-gutter: 3%,
+    details.map(((label, value)) => [#strong(label + ":") #value]).join(parbreak()),
+    [
+      #strong(client.name) \
+      #address-lines(client.address) \
+      #link("mailto:" + client.contact.email, client.contact.email)
+    ],
+  )
+}
+
+// The items and expenses of an invoice or quote with its discounts, taxes and total,
+// or the amount credited by a credit note.
+//
+// The amounts come formatted from pdoc, e.g. 29.50,
+// since Typst can only calculate with floats.
+#let items-table(document, formatted) = {
+  let item-row(line) = (line.description, line.quantity, line.unit_price, line.amount)
+  let section(title) = (table.cell(colspan: 4, strong(upper(title))),)
+  let summary(label, amount) = (table.cell(colspan: 3, align: right, label), amount)
+
+  let rows = ()
+  for line in formatted.items {
+    rows += item-row(line)
+  }
+  if formatted.expenses.len() > 0 {
+    rows += section("Expenses")
+    for line in formatted.expenses {
+      rows += item-row(line)
+    }
+  }
+  if formatted.discounts.len() > 0 {
+    rows += section("Discounts")
+    for line in formatted.discounts {
+      rows += item-row(line)
+    }
+  }
+  rows.push(table.hline(stroke: 0.5pt))
+  rows += summary("Subtotal", formatted.subtotal)
+  for tax in formatted.taxes {
+    rows += summary("Tax " + tax.rate + "%", tax.tax)
+  }
+  rows += summary(strong("Total (" + document.currency + ")"), strong(formatted.total))
+
+  table(
+    columns: (1fr, auto, auto, auto),
+    align: (x, y) => if x == 0 { left } else { right },
+    stroke: none,
+    table.header(
+      ..("Description", "Quantity", "Unit Price", "Amount").map(name => text(fill: accent, strong(upper(name)))),
+    ),
+    table.hline(stroke: 1pt + accent),
+    ..rows,
+  )
+}
+
+// Your name and address, contact information and payment methods.
+#let merchant() = {
+  let me = data.me
+  let signature = branding-image("signature")
+  let payment = me.payment.map(method => {
+    let label = method.at("display_text", default: none)
+    let label = if label == none { method.name } else { label }
+    let url = method.at("url", default: none)
+    if url == none { label } else { link(url, label) }
+  })
+
+  set text(style: "italic")
+  grid(
+    columns: (1fr, 1fr, 1fr),
+    column-gutter: 3%,
+    [
+      #if signature != none [#image(signature, height: 1.2cm) \ ]
+      #strong(me.name) \
+      #address-lines(me.address)
+    ],
+    [
+      #strong[Contact] \
+      #link("mailto:" + me.contact.email, me.contact.email) \
+      #me.contact.phone
+    ],
+    [
+      #strong[Payment] \
+      #payment.join(linebreak())
+    ],
+  )
+}
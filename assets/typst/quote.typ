@@ -0,0 +1,31 @@
+// The built-in Typst quote template.
+
+#import "pdoc.typ": *
+
+#let quote = data.quote
+#let number = str(quote.number)
+
+#show: setup.with("Quote", number)
+
+#header("Quote", quote.date, number)
+
+#parties((
+  ("Valid Until", long-date(quote.valid_until)),
+  ("Project", data.project.name),
+  ("Description", data.project.description),
+))
+
+#v(1fr)
+
+#items-table(quote, data.formatted)
+
+#v(1fr)
+
+#let conditions = quote.at("conditions", default: none)
+#if conditions != none {
+  conditions
+}
+
+#v(1fr)
+
+#merchant()
@@ -0,0 +1,41 @@
+// The built-in Typst receipt template.
+
+#import "pdoc.typ": *
+
+#let receipt = data.receipt
+#let invoice = data.invoice
+#let currency = invoice.currency
+#let number = if receipt.payment_num == 1 {
+  str(receipt.invoice_num)
+} else {
+  str(receipt.invoice_num) + "-" + str(receipt.payment_num)
+}
+
+#show: setup.with("Receipt", number)
+
+#header("Receipt", receipt.date, number)
+
+#parties((
+  ("Invoice Date", long-date(invoice.date)),
+  ("Amount Paid", data.amount_paid + " " + currency),
+  ("Balance Due", data.balance + " " + currency),
+  ("Project", data.project.name),
+  ("Description", data.project.description),
+))
+
+#v(1fr)
+
+#items-table(invoice, data.formatted)
+
+#v(1fr)
+
+Payment of #data.amount_paid #currency via #receipt.payment_method was successfully received on #long-date(receipt.date).
+#if data.paid_in_full [
+  This invoice has been paid in full. Thank you!
+] else [
+  The remaining balance of #data.balance #currency is due #long-date(invoice.due_date). Thank you!
+]
+
+#v(1fr)
+
+#merchant()
@@ -1 +1,427 @@
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write as _, io::Write, path::PathBuf};
 
+use anyhow::{bail, Context};
+use beancount_core::{Account, AccountType, Date, Posting, Transaction};
+use beancount_render::{BasicRenderer, Renderer};
+
+use crate::{
+    config::{BeancountConfig, Config},
+    date::DateString,
+    storage::get_beancount_dir,
+};
+
+/// Name of the index file which includes all generated beancount files.
+pub const INDEX_FILENAME: &str = "pdoc.beancount";
+
+/// Parse a full account name such as `Assets:Bank:Checking`.
+pub fn parse_account(name: &str) -> anyhow::Result<Account<'static>> {
+    let mut components = name.split(':');
+
+    let ty = match components.next() {
+        Some("Assets") => AccountType::Assets,
+        Some("Liabilities") => AccountType::Liabilities,
+        Some("Equity") => AccountType::Equity,
+        Some("Income") => AccountType::Income,
+        Some("Expenses") => AccountType::Expenses,
+        _ => bail!(
+            "account {:?} must start with Assets, Liabilities, Equity, Income or Expenses",
+            name
+        ),
+    };
+
+    let parts: Vec<Cow<'static, str>> =
+        components.map(|part| Cow::Owned(part.to_owned())).collect();
+
+    if parts.is_empty() || parts.iter().any(|part| part.is_empty()) {
+        bail!("account {:?} has empty components", name);
+    }
+
+    let account = Account::builder().ty(ty).parts(parts).build();
+
+    Ok(account)
+}
+
+/// Strip all non-alphanumeric characters and capitalise the first letter,
+/// so that a name can be used as a component of an account name.
+pub fn account_component(name: &str) -> String {
+    let mut chars = name.chars().filter(|c| c.is_alphanumeric());
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Strip all characters which beancount doesn't allow in tags and links,
+/// so that a name can be used in one.
+pub fn tag_component(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/' | '.'))
+        .collect()
+}
+
+/// Values for the `{client}`, `{project}`, `{year}` and `{number}`
+/// placeholders in the beancount config.
+#[derive(Clone)]
+pub struct Placeholders {
+    pub client: String,
+    pub project: String,
+    pub year: i32,
+    pub number: u32,
+}
+
+impl Placeholders {
+    fn fill_with(&self, template: &str, client: &str, project: &str) -> String {
+        template
+            .replace("{client}", client)
+            .replace("{project}", project)
+            .replace("{year}", &self.year.to_string())
+            .replace("{number}", &self.number.to_string())
+    }
+
+    /// Fill in a narration, payee or metadata template.
+    pub fn fill(&self, template: &str) -> String {
+        self.fill_with(template, &self.client, &self.project)
+    }
+
+    /// Fill in a tag or link template.
+    /// Client and project names are reduced to characters allowed in tags.
+    pub fn fill_tag(&self, template: &str) -> String {
+        self.fill_with(
+            template,
+            &tag_component(&self.client),
+            &tag_component(&self.project),
+        )
+    }
+
+    /// Fill in an account name template and parse the result.
+    /// Client and project names are reduced to valid account components.
+    pub fn fill_account(&self, template: &str) -> anyhow::Result<Account<'static>> {
+        let name = self.fill_with(
+            template,
+            &account_component(&self.client),
+            &account_component(&self.project),
+        );
+
+        parse_account(&name).with_context(|| format!("parsing account template {:?}", template))
+    }
+}
+
+/// A transaction together with the metadata from the config,
+/// which is written below the transaction header.
+pub struct ConfiguredTransaction<'a> {
+    pub txn: Transaction<'a>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl<'a> ConfiguredTransaction<'a> {
+    /// Build a transaction with the payee, tags, links and metadata from the config.
+    pub fn new(
+        config: &BeancountConfig,
+        placeholders: &Placeholders,
+        date: Date<'a>,
+        narration: String,
+        postings: Vec<Posting<'a>>,
+    ) -> Self {
+        let payee = config
+            .payee
+            .as_ref()
+            .map(|payee| placeholders.fill(payee).into());
+        let tags = config
+            .tags
+            .iter()
+            .map(|tag| placeholders.fill_tag(tag).into())
+            .collect();
+        let links = config
+            .links
+            .iter()
+            .map(|link| placeholders.fill_tag(link).into())
+            .collect();
+        let metadata = config
+            .metadata
+            .iter()
+            .map(|(key, value)| (key.clone(), placeholders.fill(value)))
+            .collect();
+
+        let txn = Transaction::builder()
+            .date(date)
+            .payee(payee)
+            .narration(narration.into())
+            .tags(tags)
+            .links(links)
+            .postings(postings)
+            .build();
+
+        Self { txn, metadata }
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        let mut rendered = Vec::<u8>::new();
+        write_transaction(&self.txn, &mut rendered)?;
+        let rendered = String::from_utf8(rendered).context("decoding rendered transaction")?;
+
+        let (header, postings) = rendered.split_once('\n').unwrap_or((&rendered, ""));
+        writeln!(writer, "{}", header)?;
+        for (key, value) in &self.metadata {
+            writeln!(writer, "\t{}: \"{}\"", key, escape_string(value))?;
+        }
+        write!(writer, "{}", postings)?;
+
+        Ok(())
+    }
+}
+
+/// Escape the contents of a beancount string literal,
+/// which only treats backslashes and double quotes specially.
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+pub fn write_transaction<W: Write>(txn: &Transaction, writer: &mut W) -> anyhow::Result<()> {
+    let renderer = BasicRenderer::new();
+
+    renderer
+        .render(txn, writer)
+        .context("rendering beancount transaction")?;
+
+    Ok(())
+}
+
+/// Collect the earliest date on which each account is used
+/// in the transactions of a generated beancount file.
+fn collect_accounts(contents: &str, accounts: &mut BTreeMap<String, String>) {
+    let mut date = None;
+
+    for line in contents.lines() {
+        let Some(first) = line.split_whitespace().next() else {
+            continue;
+        };
+
+        if !line.starts_with(char::is_whitespace) {
+            date = DateString::try_new(first.to_owned())
+                .ok()
+                .map(|_| first.to_owned());
+        } else if let (Some(date), Ok(_)) = (&date, parse_account(first)) {
+            let earliest = accounts
+                .entry(first.to_owned())
+                .or_insert_with(|| date.clone());
+            if *date < *earliest {
+                *earliest = date.clone();
+            }
+        }
+    }
+}
+
+/// Render the index from the names and contents of all generated files.
+/// Every account gets an `open` directive on the date it is first used.
+fn index_contents(files: &[(String, String)]) -> String {
+    let mut accounts = BTreeMap::new();
+    for (_, contents) in files {
+        collect_accounts(contents, &mut accounts);
+    }
+
+    let mut index = String::new();
+    _ = writeln!(index, "; Generated by pdoc, do not edit.");
+
+    if !accounts.is_empty() {
+        _ = writeln!(index);
+        for (account, date) in &accounts {
+            _ = writeln!(index, "{} open {}", date, account);
+        }
+    }
+
+    if !files.is_empty() {
+        _ = writeln!(index);
+        for (filename, _) in files {
+            _ = writeln!(index, "include {:?}", filename);
+        }
+    }
+
+    index
+}
+
+/// Regenerate the index file from all beancount files in the beancount directory.
+pub fn rebuild_index(config: &Config) -> anyhow::Result<PathBuf> {
+    let beancount_dir = get_beancount_dir(config).context("getting beancount directory")?;
+
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(&beancount_dir).context("reading beancount directory")?;
+    for entry in entries {
+        let path = entry.context("reading beancount directory entry")?.path();
+        let Some(filename) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if filename == INDEX_FILENAME || !filename.ends_with(".beancount") {
+            continue;
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading beancount file {:?}", path))?;
+        files.push((filename.to_owned(), contents));
+    }
+    files.sort();
+
+    let index_path = beancount_dir.join(INDEX_FILENAME);
+    std::fs::write(&index_path, index_contents(&files)).context("writing beancount index")?;
+
+    Ok(index_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use beancount_core::{Account, AccountType};
+
+    use crate::config::BeancountConfig;
+
+    use super::{
+        account_component, index_contents, parse_account, tag_component, ConfiguredTransaction,
+        Placeholders,
+    };
+
+    fn test_placeholders() -> Placeholders {
+        Placeholders {
+            client: "Test Client #1".to_owned(),
+            project: "Test Project #1".to_owned(),
+            year: 2023,
+            number: 17,
+        }
+    }
+
+    #[test]
+    fn test_parse_account() -> anyhow::Result<()> {
+        let expected = Account::builder()
+            .ty(AccountType::Assets)
+            .parts([Cow::Borrowed("Bank"), Cow::Borrowed("Checking")].to_vec())
+            .build();
+
+        assert_eq!(parse_account("Assets:Bank:Checking")?, expected);
+        assert!(parse_account("Bank:Checking").is_err());
+        assert!(parse_account("Assets").is_err());
+        assert!(parse_account("Assets::Checking").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_component() {
+        assert_eq!(account_component("Test Client #1"), "TestClient1");
+        assert_eq!(account_component("travel costs"), "Travelcosts");
+        assert_eq!(account_component("éclair"), "Éclair");
+    }
+
+    #[test]
+    fn test_tag_component() {
+        assert_eq!(tag_component("Test Client #1"), "TestClient1");
+        assert_eq!(tag_component("acme-corp/web.site"), "acme-corp/web.site");
+        assert_eq!(tag_component("Café"), "Caf");
+    }
+
+    #[test]
+    fn test_fill_placeholders() -> anyhow::Result<()> {
+        let placeholders = test_placeholders();
+
+        assert_eq!(
+            placeholders.fill("Invoice #{number} - {project} ({year})"),
+            "Invoice #17 - Test Project #1 (2023)"
+        );
+
+        let expected = Account::builder()
+            .ty(AccountType::Income)
+            .parts([Cow::Borrowed("TestClient1"), Cow::Borrowed("2023")].to_vec())
+            .build();
+        assert_eq!(
+            placeholders.fill_account("Income:{client}:{year}")?,
+            expected
+        );
+        assert!(placeholders.fill_account("{client}:Income").is_err());
+
+        assert_eq!(
+            placeholders.fill_tag("invoice-{number}-{project}"),
+            "invoice-17-TestProject1"
+        );
+
+        let lowercase = Placeholders {
+            client: "acme corp".to_owned(),
+            ..placeholders
+        };
+        let expected = Account::builder()
+            .ty(AccountType::Income)
+            .parts([Cow::Borrowed("Acmecorp")].to_vec())
+            .build();
+        assert_eq!(lowercase.fill_account("Income:{client}")?, expected);
+    }
+
+    #[test]
+    fn test_write_metadata() -> anyhow::Result<()> {
+        let mut config = BeancountConfig::default();
+        config
+            .metadata
+            .insert("invoice".to_owned(), "{number}".to_owned());
+        config
+            .metadata
+            .insert("note".to_owned(), "Café \"{client}\" \\o/".to_owned());
+        let placeholders = test_placeholders();
+        let txn = ConfiguredTransaction::new(
+            &config,
+            &placeholders,
+            beancount_core::Date::from_str_unchecked("2023-01-07"),
+            placeholders.fill(&config.invoice_narration),
+            Vec::new(),
+        );
+
+        let mut buf = Vec::<u8>::new();
+        txn.write_to(&mut buf)?;
+
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "2023-01-07 * \"Invoice #17 - Test Project #1\"\n\
+             \tinvoice: \"17\"\n\
+             \tnote: \"Café \\\"Test Client #1\\\" \\\\o/\"\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_contents() {
+        let files = [
+            (
+                "Invoice_17.beancount".to_owned(),
+                "2023-01-07 * \"Invoice #17 - Test Project #1\"\n\
+                 \tinvoice: \"17\"\n\
+                 \tIncome:TestClient1\t-29.50 USD\n\
+                 \tAssets:AccountsReceivable\t29.50 USD\n"
+                    .to_owned(),
+            ),
+            (
+                "Receipt_17.beancount".to_owned(),
+                "2023-01-30 * \"Payment for Invoice #17 - Test Project #1\"\n\
+                 \tAssets:PayPal\t29.50 USD\n\
+                 \tAssets:AccountsReceivable\t-29.50 USD\n"
+                    .to_owned(),
+            ),
+        ];
+
+        let expected = r#"; Generated by pdoc, do not edit.
+
+2023-01-07 open Assets:AccountsReceivable
+2023-01-30 open Assets:PayPal
+2023-01-07 open Income:TestClient1
+
+include "Invoice_17.beancount"
+include "Receipt_17.beancount"
+"#;
+
+        assert_eq!(index_contents(&files), expected);
+    }
+}
@@ -0,0 +1,600 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use time::Date;
+
+use crate::{
+    cli::print_header,
+    config::Config,
+    date::DateString,
+    id::Id,
+    invoice::{FullInvoice, Invoice, LineItem, Totals},
+    money::{serialize_amount, Currency, Money},
+    project::Project,
+    receipt::{FullReceipt, Receipt, ReceiptNumber},
+    report::ReportRow,
+    storage::find_project,
+    text::{render_invoice, TextStyle},
+};
+
+/// Whether an invoice has been paid off, after credit notes and payments.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Paid,
+    /// Not paid off yet, including overdue invoices
+    Unpaid,
+    /// Not paid off by the due date
+    Overdue,
+}
+
+impl PaymentStatus {
+    pub fn of(balance: Money, due_date: Date, today: Date) -> Self {
+        if balance <= Money::ZERO {
+            Self::Paid
+        } else if due_date < today {
+            Self::Overdue
+        } else {
+            Self::Unpaid
+        }
+    }
+
+    /// Whether an invoice with this status is listed when filtering by `filter`.
+    pub fn matches(&self, filter: PaymentStatus) -> bool {
+        match filter {
+            Self::Unpaid => *self != Self::Paid,
+            _ => *self == filter,
+        }
+    }
+}
+
+impl Display for PaymentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            Self::Paid => "paid",
+            Self::Unpaid => "unpaid",
+            Self::Overdue => "overdue",
+        };
+
+        f.write_str(status)
+    }
+}
+
+/// Filters for listing invoices and receipts.
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct ListFilters {
+    /// Only list documents for this client
+    #[arg(long)]
+    pub client: Option<Id>,
+    /// Only list documents for this project
+    #[arg(long)]
+    pub project: Option<Id>,
+    /// Only list documents dated on or after this date
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    pub from: Option<DateString>,
+    /// Only list documents dated on or before this date
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    pub to: Option<DateString>,
+    /// Only list documents whose client, project or line items contain this text
+    #[arg(long)]
+    pub search: Option<String>,
+}
+
+impl ListFilters {
+    /// Whether a document of the given client and project, dated `date`
+    /// and containing the given texts, passes all filters.
+    pub fn matches<'a>(
+        &self,
+        client: &Id,
+        project: &Id,
+        date: &DateString,
+        texts: impl IntoIterator<Item = &'a str>,
+    ) -> bool {
+        // Dates as YYYY-MM-DD compare in the same order as the dates themselves.
+        let date = date.as_str();
+
+        self.client.as_ref().map_or(true, |filter| filter == client)
+            && self
+                .project
+                .as_ref()
+                .map_or(true, |filter| filter == project)
+            && self
+                .from
+                .as_ref()
+                .map_or(true, |from| date >= from.as_str())
+            && self.to.as_ref().map_or(true, |to| date <= to.as_str())
+            && self.search.as_ref().map_or(true, |search| {
+                let search = search.to_lowercase();
+                let client = client.to_string();
+                let project = project.to_string();
+
+                [client.as_str(), project.as_str()]
+                    .into_iter()
+                    .chain(texts)
+                    .any(|text| text.to_lowercase().contains(&search))
+            })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProjectRow {
+    pub name: Id,
+    pub client: Id,
+    pub description: String,
+}
+
+impl ReportRow for ProjectRow {
+    const HEADERS: &'static [&'static str] = &["Project", "Client", "Description"];
+    const TEXT_COLUMNS: usize = 3;
+
+    fn cells(&self) -> Vec<String> {
+        [
+            self.name.to_string(),
+            self.client.to_string(),
+            self.description.clone(),
+        ]
+        .to_vec()
+    }
+}
+
+/// All projects, optionally only those of one client, sorted by name.
+pub fn list_projects(client: Option<&Id>, config: &Config) -> anyhow::Result<Vec<ProjectRow>> {
+    let mut names = Project::list(config).context("listing projects")?;
+    names.sort_by_key(|name| name.to_string());
+
+    let mut rows = Vec::new();
+    for name in names {
+        let project =
+            find_project(&name, config).with_context(|| format!("finding project {:?}", name))?;
+        if client.is_some_and(|client| *client != project.client_ref) {
+            continue;
+        }
+
+        rows.push(ProjectRow {
+            name: project.name,
+            client: project.client_ref,
+            description: project.description,
+        });
+    }
+
+    Ok(rows)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct InvoiceRow {
+    pub number: u32,
+    pub date: DateString,
+    pub due_date: DateString,
+    pub client: Id,
+    pub project: Id,
+    pub status: PaymentStatus,
+    pub currency: Currency,
+    #[serde(serialize_with = "serialize_amount")]
+    pub total: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub balance: Money,
+}
+
+impl ReportRow for InvoiceRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Invoice", "Date", "Due", "Client", "Project", "Status", "Currency", "Total", "Balance",
+    ];
+    const TEXT_COLUMNS: usize = 7;
+
+    fn cells(&self) -> Vec<String> {
+        [
+            self.number.to_string(),
+            self.date.as_str().to_owned(),
+            self.due_date.as_str().to_owned(),
+            self.client.to_string(),
+            self.project.to_string(),
+            self.status.to_string(),
+            self.currency.to_string(),
+            self.total.to_string(),
+            self.balance.to_string(),
+        ]
+        .to_vec()
+    }
+}
+
+fn item_descriptions(invoice: &Invoice) -> impl Iterator<Item = &str> {
+    invoice
+        .items
+        .iter()
+        .chain(&invoice.expenses)
+        .map(|item| item.description.as_str())
+}
+
+/// All invoices passing the filters, sorted by number.
+pub fn list_invoices(
+    filters: &ListFilters,
+    status: Option<PaymentStatus>,
+    today: Date,
+    config: &Config,
+) -> anyhow::Result<Vec<InvoiceRow>> {
+    let mut numbers = Invoice::list(config).context("listing invoices")?;
+    numbers.sort();
+
+    let mut rows = Vec::new();
+    for number in numbers {
+        let full_invoice = Invoice::load(number, config)
+            .and_then(|invoice| invoice.collect(config))
+            .with_context(|| format!("loading invoice {}", number))?;
+        let invoice = &full_invoice.invoice;
+        if !filters.matches(
+            &full_invoice.client.name,
+            &full_invoice.project.name,
+            &invoice.date,
+            item_descriptions(invoice),
+        ) {
+            continue;
+        }
+
+        let balance = invoice
+            .outstanding_balance(config)
+            .with_context(|| format!("getting balance of invoice {}", number))?;
+        let due_date: Date = invoice
+            .due_date
+            .clone()
+            .try_into()
+            .with_context(|| format!("parsing due date of invoice {}", number))?;
+        let invoice_status = PaymentStatus::of(balance, due_date, today);
+        if status.is_some_and(|status| !invoice_status.matches(status)) {
+            continue;
+        }
+
+        rows.push(InvoiceRow {
+            number,
+            date: invoice.date.clone(),
+            due_date: invoice.due_date.clone(),
+            client: full_invoice.client.name.clone(),
+            project: full_invoice.project.name.clone(),
+            status: invoice_status,
+            currency: invoice.currency.clone(),
+            total: invoice.total(),
+            balance,
+        });
+    }
+
+    Ok(rows)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReceiptRow {
+    pub number: String,
+    pub date: DateString,
+    pub invoice_num: u32,
+    pub client: Id,
+    pub project: Id,
+    pub payment_method: String,
+    pub currency: Currency,
+    #[serde(serialize_with = "serialize_amount")]
+    pub amount: Money,
+}
+
+impl ReportRow for ReceiptRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Receipt", "Date", "Invoice", "Client", "Project", "Method", "Currency", "Amount",
+    ];
+    const TEXT_COLUMNS: usize = 7;
+
+    fn cells(&self) -> Vec<String> {
+        [
+            self.number.clone(),
+            self.date.as_str().to_owned(),
+            self.invoice_num.to_string(),
+            self.client.to_string(),
+            self.project.to_string(),
+            self.payment_method.clone(),
+            self.currency.to_string(),
+            self.amount.to_string(),
+        ]
+        .to_vec()
+    }
+}
+
+/// All receipts passing the filters, sorted by number.
+pub fn list_receipts(filters: &ListFilters, config: &Config) -> anyhow::Result<Vec<ReceiptRow>> {
+    let mut numbers = Receipt::list(config).context("listing receipts")?;
+    numbers.sort();
+
+    let mut rows = Vec::new();
+    for number in numbers {
+        let full_receipt = Receipt::load(number, config)
+            .and_then(|receipt| receipt.collect(config))
+            .with_context(|| format!("loading receipt {}", number))?;
+        if !filters.matches(
+            &full_receipt.client.name,
+            &full_receipt.project.name,
+            &full_receipt.receipt.date,
+            item_descriptions(&full_receipt.invoice),
+        ) {
+            continue;
+        }
+
+        rows.push(ReceiptRow {
+            number: number.to_string(),
+            date: full_receipt.receipt.date.clone(),
+            invoice_num: full_receipt.invoice.number,
+            client: full_receipt.client.name.clone(),
+            project: full_receipt.project.name.clone(),
+            payment_method: full_receipt.receipt.payment_method.clone(),
+            currency: full_receipt.invoice.currency.clone(),
+            amount: full_receipt.amount_paid,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// How `pdoc show` prints a document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum ShowFormat {
+    /// A summary for reading in a terminal
+    #[default]
+    Human,
+    Yaml,
+    Json,
+    /// Aligned plain text for pasting into emails, for invoices
+    Text,
+    /// Markdown for pasting into tickets and chat, for invoices
+    Markdown,
+}
+
+impl ShowFormat {
+    /// Print a document as YAML or JSON, or as a summary with `print_human`.
+    fn print<T: Serialize>(&self, value: &T, print_human: impl Fn(&T)) -> anyhow::Result<()> {
+        match self {
+            Self::Human => print_human(value),
+            Self::Yaml => print!(
+                "{}",
+                serde_yaml::to_string(value).context("serializing yaml")?
+            ),
+            Self::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(value).context("serializing json")?
+            ),
+            Self::Text | Self::Markdown => {
+                bail!("text and markdown output is only available for invoices")
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn print_items(title: &str, items: &[LineItem]) {
+    if items.is_empty() {
+        return;
+    }
+
+    println!("\n{}:", title);
+    for item in items {
+        println!(
+            "- {}: {} x {} = {}",
+            item.description,
+            item.quantity,
+            item.unit_price,
+            item.subtotal()
+        );
+    }
+}
+
+/// An invoice with everything computed from it and the stored credit notes and receipts.
+#[derive(Clone, Debug, Serialize)]
+pub struct InvoiceDetails {
+    #[serde(flatten)]
+    pub full_invoice: FullInvoice,
+    pub totals: Totals,
+    /// The amount taken off of the total by credit notes.
+    #[serde(serialize_with = "serialize_amount")]
+    pub credited: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub paid: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub balance: Money,
+    pub status: PaymentStatus,
+}
+
+impl InvoiceDetails {
+    pub fn load(number: u32, today: Date, config: &Config) -> anyhow::Result<Self> {
+        let invoice = Invoice::load(number, config).context("loading invoice")?;
+        let totals = invoice.totals();
+        let amount_due = invoice
+            .amount_due(config)
+            .context("getting amount due after credit notes")?;
+        let balance = invoice
+            .outstanding_balance(config)
+            .context("getting outstanding balance")?;
+        let due_date: Date = invoice
+            .due_date
+            .clone()
+            .try_into()
+            .context("parsing invoice due date")?;
+        let full_invoice = invoice
+            .collect(config)
+            .context("collecting all invoice information")?;
+
+        let details = Self {
+            credited: totals.total - amount_due,
+            paid: amount_due - balance,
+            status: PaymentStatus::of(balance, due_date, today),
+            full_invoice,
+            totals,
+            balance,
+        };
+        Ok(details)
+    }
+
+    fn print_human(&self) {
+        let invoice = &self.full_invoice.invoice;
+        let currency = &invoice.currency;
+
+        print_header(&format!("Invoice {}", invoice.number));
+        println!("Date:     {}", invoice.date);
+        println!("Due:      {}", invoice.due_date);
+        println!("Client:   {}", self.full_invoice.client.name);
+        println!("Project:  {}", self.full_invoice.project.name);
+        println!("Status:   {}", self.status);
+
+        print_items("Items", &invoice.items);
+        print_items("Expenses", &invoice.expenses);
+
+        println!();
+        println!("Subtotal: {} {}", self.totals.subtotal, currency);
+        for discount in &self.totals.discounts {
+            println!(
+                "Discount: -{} {} ({})",
+                discount.amount, currency, discount.description
+            );
+        }
+        for tax in &self.totals.taxes {
+            println!("Tax {}%: {} {}", tax.rate, tax.tax, currency);
+        }
+        println!("Total:    {} {}", self.totals.total, currency);
+        if !self.credited.is_zero() {
+            println!("Credited: {} {}", self.credited, currency);
+        }
+        println!("Paid:     {} {}", self.paid, currency);
+        println!("Balance:  {} {}", self.balance, currency);
+
+        if let Some(conditions) = &invoice.conditions {
+            println!("\n{}", conditions);
+        }
+    }
+
+    pub fn print(&self, format: ShowFormat) -> anyhow::Result<()> {
+        match format {
+            ShowFormat::Text => print!("{}", render_invoice(&self.full_invoice, TextStyle::Plain)),
+            ShowFormat::Markdown => print!(
+                "{}",
+                render_invoice(&self.full_invoice, TextStyle::Markdown)
+            ),
+            _ => format.print(self, Self::print_human)?,
+        }
+
+        Ok(())
+    }
+}
+
+fn print_receipt_human(full_receipt: &FullReceipt) {
+    let currency = &full_receipt.invoice.currency;
+
+    print_header(&format!("Receipt {}", full_receipt.receipt.number()));
+    println!("Date:     {}", full_receipt.receipt.date);
+    println!("Invoice:  {}", full_receipt.invoice.number);
+    println!("Client:   {}", full_receipt.client.name);
+    println!("Project:  {}", full_receipt.project.name);
+    println!("Method:   {}", full_receipt.receipt.payment_method);
+    println!("Paid:     {} {}", full_receipt.amount_paid, currency);
+    println!("Balance:  {} {}", full_receipt.balance, currency);
+}
+
+pub fn show_receipt(
+    number: ReceiptNumber,
+    format: ShowFormat,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let full_receipt = Receipt::load(number, config)
+        .context("loading receipt")?
+        .collect(config)
+        .context("collecting all receipt information")?;
+
+    format.print(&full_receipt, print_receipt_human)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use crate::{date::DateString, money::Money, test_util::create_full_test_invoice};
+
+    use super::{InvoiceDetails, ListFilters, PaymentStatus};
+
+    #[test]
+    fn test_payment_status() -> anyhow::Result<()> {
+        let today = date!(2023 - 01 - 22);
+        let owed: Money = "10".parse()?;
+
+        assert_eq!(
+            PaymentStatus::of(Money::ZERO, date!(2023 - 01 - 21), today),
+            PaymentStatus::Paid
+        );
+        assert_eq!(
+            PaymentStatus::of(owed, date!(2023 - 01 - 22), today),
+            PaymentStatus::Unpaid
+        );
+        assert_eq!(
+            PaymentStatus::of(owed, date!(2023 - 01 - 21), today),
+            PaymentStatus::Overdue
+        );
+
+        assert!(PaymentStatus::Overdue.matches(PaymentStatus::Unpaid));
+        assert!(!PaymentStatus::Unpaid.matches(PaymentStatus::Overdue));
+        assert!(!PaymentStatus::Paid.matches(PaymentStatus::Unpaid));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invoice_details_json() -> anyhow::Result<()> {
+        let full_invoice = create_full_test_invoice();
+        let totals = full_invoice.invoice.totals();
+        let paid: Money = "10".parse()?;
+        let details = InvoiceDetails {
+            full_invoice,
+            credited: Money::ZERO,
+            paid,
+            balance: totals.total - paid,
+            totals,
+            status: PaymentStatus::Unpaid,
+        };
+
+        // Amounts are printed like in `pdoc list --format json`
+        let json = serde_json::to_value(&details)?;
+        assert_eq!(json["totals"]["total"], "29.50");
+        assert_eq!(json["credited"], "0.00");
+        assert_eq!(json["paid"], "10.00");
+        assert_eq!(json["balance"], "19.50");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_filters() -> anyhow::Result<()> {
+        let client = "Test Client #1".to_owned().into();
+        let project = "Test Project #1".to_owned().into();
+        let date: DateString = "2023-01-07".parse()?;
+        let items = ["Test the first thing"];
+
+        let filters = ListFilters {
+            client: Some("Test Client #1".to_owned().into()),
+            from: Some("2023-01-01".parse()?),
+            to: Some("2023-01-31".parse()?),
+            search: Some("FIRST".to_owned()),
+            ..Default::default()
+        };
+        assert!(filters.matches(&client, &project, &date, items));
+
+        let other_client = ListFilters {
+            client: Some("Someone else".to_owned().into()),
+            ..Default::default()
+        };
+        assert!(!other_client.matches(&client, &project, &date, items));
+
+        let too_late = ListFilters {
+            from: Some("2023-01-08".parse()?),
+            ..Default::default()
+        };
+        assert!(!too_late.matches(&client, &project, &date, items));
+
+        let no_match = ListFilters {
+            search: Some("third".to_owned()),
+            ..Default::default()
+        };
+        assert!(!no_match.matches(&client, &project, &date, items));
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{fmt::Display, marker::PhantomData};
 
 use inquire::validator::{StringValidator, Validation};
 use serde::Deserialize;
@@ -32,6 +32,18 @@ pub fn print_header(text: &str) {
     println!("{}", formatted);
 }
 
+/// A choice in a select prompt, shown as its description.
+pub struct SelectOption<T> {
+    pub value: T,
+    pub description: String,
+}
+
+impl<T> Display for SelectOption<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{format_header, format_title};
@@ -1,6 +1,6 @@
 use std::{fs::File, path::Path};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -10,7 +10,9 @@ use crate::{
     config::Config,
     contact::ContactInfo,
     id::Id,
-    storage::get_clients_dir,
+    money::{Currency, Money, Percentage},
+    project::Project,
+    storage::{find_project, get_clients_dir},
 };
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -19,6 +21,18 @@ pub struct Client {
     pub name: Id,
     pub address: MailingAddress,
     pub contact: ContactInfo,
+    /// Currency used for this client's invoices unless the project overrides it.
+    pub currency: Option<Currency>,
+    /// Tax rate (in percent) for this client's invoices, overriding `config.toml`.
+    pub tax_rate: Option<Percentage>,
+    /// Unit price for new line items unless the project overrides it.
+    pub hourly_rate: Option<Money>,
+    /// Days after the invoice date that payment is due unless the project overrides it.
+    pub days_to_pay: Option<u16>,
+    /// Terms and conditions for new invoices unless the project overrides them.
+    pub conditions: Option<String>,
+    /// Template set used for this client's documents unless the project overrides it.
+    pub template: Option<String>,
 }
 
 impl Client {
@@ -78,6 +92,12 @@ impl Client {
             name,
             address,
             contact,
+            currency: None,
+            tax_rate: None,
+            hourly_rate: None,
+            days_to_pay: None,
+            conditions: None,
+            template: None,
         };
 
         client = client.edit_yaml().context("editing client yaml")?;
@@ -115,6 +135,86 @@ impl Client {
         Ok(client)
     }
 
+    /// Names of all projects for this client.
+    pub fn projects(name: &Id, config: &Config) -> anyhow::Result<Vec<Id>> {
+        let mut projects = Vec::new();
+        for project_name in Project::list(config).context("listing projects")? {
+            let project = find_project(&project_name, config)
+                .with_context(|| format!("finding project {}", project_name))?;
+            if project.client_ref == *name {
+                projects.push(project_name);
+            }
+        }
+        projects.sort_by_key(ToString::to_string);
+
+        Ok(projects)
+    }
+
+    /// Rename a client, updating all of its projects.
+    pub fn rename(old: &Id, new: Id, config: &Config) -> anyhow::Result<()> {
+        let mut client = Client::load(old.clone(), config).context("loading client")?;
+        if Self::list(config)?
+            .iter()
+            .any(|name| name != old && name.eq_ignore_case(&new))
+        {
+            bail!("a client named {} already exists", new);
+        }
+
+        // Repoint the projects first: if one fails to save,
+        // the old client is still there to retry the rename with.
+        let projects = Self::projects(old, config).context("listing client's projects")?;
+        for project_name in projects {
+            let mut project = find_project(&project_name, config)
+                .with_context(|| format!("finding project {}", project_name))?;
+            project.client_ref = new.clone();
+            project
+                .save(config)
+                .with_context(|| format!("saving project {}", project_name))?;
+        }
+
+        // Saving under the new name and deleting the old file would delete
+        // the client when only the case changes on a case-insensitive filesystem.
+        let clients_dir = get_clients_dir(config).context("getting clients directory")?;
+        std::fs::rename(
+            clients_dir.join(old.to_filename()),
+            clients_dir.join(new.to_filename()),
+        )
+        .context("renaming client file")?;
+        client.name = new;
+        client.save(config).context("saving renamed client")?;
+
+        Ok(())
+    }
+
+    /// Delete a client. Fails if it still has projects, unless `force` is given,
+    /// which deletes the projects and everything referring to them as well.
+    pub fn delete(name: &Id, force: bool, config: &Config) -> anyhow::Result<()> {
+        Client::load(name.clone(), config).context("loading client")?;
+
+        let projects = Self::projects(name, config).context("listing client's projects")?;
+        if !projects.is_empty() {
+            if !force {
+                let names: Vec<String> = projects.iter().map(ToString::to_string).collect();
+                bail!(
+                    "client {} still has projects {} (use --force to delete them too)",
+                    name,
+                    names.join(", ")
+                );
+            }
+
+            for project_name in projects {
+                Project::delete(&project_name, true, config)
+                    .with_context(|| format!("deleting project {}", project_name))?;
+            }
+        }
+
+        let clients_dir = get_clients_dir(config).context("getting clients directory")?;
+        std::fs::remove_file(clients_dir.join(name.to_filename()))
+            .context("removing client file")?;
+
+        Ok(())
+    }
+
     pub fn list(config: &Config) -> anyhow::Result<Vec<Id>> {
         let clients_dir = get_clients_dir(config).context("getting clients directory")?;
 
@@ -1,19 +1,100 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use anyhow::Context;
 use serde::Deserialize;
 
-use crate::{me::Me, storage::get_config_file_path};
+use crate::{latex::Renderer, me::Me, money::Percentage, storage::get_config_file_path};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct StorageConfig {
     pub data_dir: Option<PathBuf>,
 }
 
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct InvoiceConfig {
+    /// Tax rate (in percent) for new invoices, unless the client specifies one.
+    pub tax_rate: Option<Percentage>,
+}
+
+/// User-supplied templates, see [`crate::templates`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplatesConfig {
+    /// Directory containing one subdirectory per template set,
+    /// defaulting to `templates` in the data directory.
+    pub dir: Option<PathBuf>,
+    /// Template set used unless the project or client selects one.
+    pub default: Option<String>,
+}
+
+/// How PDFs are generated.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PdfConfig {
+    pub renderer: Renderer,
+}
+
+/// Settings for the generated beancount transactions.
+///
+/// Account names, the narrations, the payee, tags, links and metadata values
+/// may contain the placeholders `{client}`, `{project}`, `{year}` and `{number}`
+/// (the invoice number).
+/// The expense account and narration may also contain `{category}` and `{description}`,
+/// in which case `{number}` is the expense number.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeancountConfig {
+    /// Whether to write beancount files at all.
+    pub enabled: bool,
+    pub income_account: String,
+    pub receivable_account: String,
+    pub tax_account: String,
+    pub invoice_narration: String,
+    pub receipt_narration: String,
+    pub credit_note_narration: String,
+    pub expense_account: String,
+    /// Account that expenses are paid from, unless an expense specifies its own.
+    pub expense_payment_account: String,
+    pub expense_narration: String,
+    pub payee: Option<String>,
+    pub tags: Vec<String>,
+    pub links: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Default for BeancountConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            income_account: "Income:{client}".to_owned(),
+            receivable_account: "Assets:AccountsReceivable".to_owned(),
+            tax_account: "Liabilities:SalesTax".to_owned(),
+            invoice_narration: "Invoice #{number} - {project}".to_owned(),
+            receipt_narration: "Payment for Invoice #{number} - {project}".to_owned(),
+            credit_note_narration: "Credit Note for Invoice #{number} - {project}".to_owned(),
+            expense_account: "Expenses:{category}".to_owned(),
+            expense_payment_account: "Assets:Checking".to_owned(),
+            expense_narration: "{description} - {project}".to_owned(),
+            payee: None,
+            tags: Vec::new(),
+            links: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     pub me: Me,
     pub storage: StorageConfig,
+    #[serde(default)]
+    pub invoice: InvoiceConfig,
+    #[serde(default)]
+    pub beancount: BeancountConfig,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+    #[serde(default)]
+    pub pdf: PdfConfig,
 }
 
 impl Config {
@@ -0,0 +1,695 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use askama::Template;
+use beancount_core::{Amount, Posting};
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use crate::{
+    beancount::{rebuild_index, ConfiguredTransaction, Placeholders},
+    cli::{print_header, SelectOption, YamlValidator},
+    client::Client,
+    config::{BeancountConfig, Config},
+    date::DateString,
+    invoice::{FormattedLine, FormattedTax, FormattedTotals, Invoice, TaxLine, Totals},
+    latex::{Document, Latex},
+    me::Me,
+    money::{serialize_amount, Money},
+    project::Project,
+    receipt::{amount_paid, Receipt},
+    storage::{
+        find_client, find_invoice, find_project, get_beancount_dir, get_credit_notes_dir,
+        get_pdfs_dir,
+    },
+};
+
+/// A credit note reverses all or part of an issued invoice,
+/// which itself stays unchanged.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreditNote {
+    pub number: u32,
+    pub invoice_num: u32,
+    pub date: DateString,
+    pub reason: String,
+    /// Amount credited, including tax.
+    /// Credit notes without an amount credit the rest of the invoice.
+    #[serde(default)]
+    pub amount: Option<Money>,
+}
+
+/// The amount credited by each of a list of credit notes for the same invoice, in order.
+pub fn amounts_credited<'a>(
+    credit_notes: impl IntoIterator<Item = &'a CreditNote>,
+    total: Money,
+) -> Vec<Money> {
+    let mut credited = Money::ZERO;
+
+    credit_notes
+        .into_iter()
+        .map(|credit_note| {
+            let amount = credit_note.amount.unwrap_or(total - credited);
+            credited = credited + amount;
+            amount
+        })
+        .collect()
+}
+
+/// The total credited by a list of credit notes for the same invoice, in order.
+pub fn amount_credited<'a>(
+    credit_notes: impl IntoIterator<Item = &'a CreditNote>,
+    total: Money,
+) -> Money {
+    amounts_credited(credit_notes, total).into_iter().sum()
+}
+
+/// The part of an invoice reversed by a credit note.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CreditTotals {
+    #[serde(serialize_with = "serialize_amount")]
+    pub before_tax: Money,
+    /// Tax credited for each tax rate of the invoice.
+    pub taxes: Vec<TaxLine>,
+    #[serde(serialize_with = "serialize_amount")]
+    pub total: Money,
+}
+
+impl CreditTotals {
+    /// Split an amount credited against an invoice into the part before tax
+    /// and the tax for each rate, in proportion to the invoice totals.
+    pub fn compute(invoice_totals: &Totals, amount: Money) -> Self {
+        let share = |money: Money| {
+            if invoice_totals.total.is_zero() {
+                Money::ZERO
+            } else {
+                let ratio = amount.as_decimal() / invoice_totals.total.as_decimal();
+                Money::new(money.as_decimal() * ratio).round_to_cents()
+            }
+        };
+
+        let taxes: Vec<TaxLine> = invoice_totals
+            .taxes
+            .iter()
+            .map(|line| TaxLine {
+                rate: line.rate,
+                taxable: share(line.taxable),
+                tax: share(line.tax),
+            })
+            .collect();
+        let tax: Money = taxes.iter().map(|line| line.tax).sum();
+
+        Self {
+            before_tax: amount - tax,
+            taxes,
+            total: amount,
+        }
+    }
+
+    pub fn tax(&self) -> Money {
+        self.taxes.iter().map(|line| line.tax).sum()
+    }
+
+    /// The credited amount as a single line with a negative price, followed by
+    /// the credited tax for each rate, the way the built-in template prints it.
+    pub fn formatted(&self, reason: &str) -> FormattedTotals {
+        let before_tax = format!("-{}", self.before_tax);
+
+        FormattedTotals {
+            items: vec![FormattedLine {
+                description: reason.to_owned(),
+                quantity: "1".to_owned(),
+                unit_price: before_tax.clone(),
+                amount: before_tax.clone(),
+            }],
+            expenses: Vec::new(),
+            discounts: Vec::new(),
+            subtotal: before_tax,
+            taxes: self
+                .taxes
+                .iter()
+                .map(|line| FormattedTax {
+                    rate: line.rate.to_string(),
+                    tax: format!("-{}", line.tax),
+                })
+                .collect(),
+            total: format!("-{}", self.total),
+        }
+    }
+}
+
+impl CreditNote {
+    pub fn list(config: &Config) -> anyhow::Result<Vec<u32>> {
+        let credit_notes_dir =
+            get_credit_notes_dir(config).context("getting credit notes directory")?;
+
+        let numbers: Vec<u32> = credit_notes_dir
+            .read_dir()
+            .context("listing credit note files")?
+            .filter_map(|entry_res| {
+                let entry = entry_res.ok()?;
+                let path = entry.path();
+                let stem = path.file_stem()?.to_string_lossy();
+                let number = stem.parse().ok()?;
+
+                Some(number)
+            })
+            .collect();
+
+        Ok(numbers)
+    }
+
+    pub fn exists(number: u32, config: &Config) -> anyhow::Result<bool> {
+        Self::list(config).map(|numbers| numbers.contains(&number))
+    }
+
+    /// Load all credit notes for an invoice, in order.
+    pub fn list_for_invoice(invoice_num: u32, config: &Config) -> anyhow::Result<Vec<Self>> {
+        let mut numbers = Self::list(config).context("listing credit notes")?;
+        numbers.sort();
+
+        let mut credit_notes = Vec::new();
+        for number in numbers {
+            let credit_note = Self::load(number, config)
+                .with_context(|| format!("loading credit note {}", number))?;
+            if credit_note.invoice_num == invoice_num {
+                credit_notes.push(credit_note);
+            }
+        }
+
+        Ok(credit_notes)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref()).context("opening credit note file")?;
+        let credit_note: CreditNote =
+            serde_yaml::from_reader(file).context("parsing credit note yaml")?;
+
+        Ok(credit_note)
+    }
+
+    pub fn load(number: u32, config: &Config) -> anyhow::Result<Self> {
+        let credit_notes_dir =
+            get_credit_notes_dir(config).context("getting credit notes directory")?;
+        let filename = format!("{}.yaml", number);
+        let path = credit_notes_dir.join(filename);
+        let credit_note = Self::load_from_path(path).context("loading credit note from file")?;
+
+        Ok(credit_note)
+    }
+
+    pub fn get_next_number(config: &Config) -> anyhow::Result<u32> {
+        let existing_numbers = Self::list(config).context("listing credit notes")?;
+        let max = existing_numbers.iter().fold(0, |acc, &el| acc.max(el));
+
+        Ok(max + 1)
+    }
+
+    pub fn edit_yaml(&self) -> anyhow::Result<Self> {
+        let yaml = serde_yaml::to_string(&self).context("serializing credit note yaml")?;
+
+        print_header("Final YAML");
+        println!("{}", yaml);
+
+        let yaml_validator = YamlValidator::<CreditNote>::new();
+
+        let edited = inquire::Editor::new("Edit...")
+            .with_predefined_text(&yaml)
+            .with_validator(yaml_validator)
+            .with_file_extension(".yaml")
+            .prompt()
+            .context("reading edited credit note yaml from user input")?;
+
+        let parsed =
+            serde_yaml::from_str(&edited).context("deserializing edited credit note yaml")?;
+
+        Ok(parsed)
+    }
+
+    pub fn create_from_user_input(config: &Config) -> anyhow::Result<Self> {
+        let invoice_nums = Invoice::list(config).context("listing invoices")?;
+
+        let mut invoice_options: Vec<SelectOption<(u32, Money)>> = invoice_nums
+            .into_iter()
+            .filter_map(|number| {
+                // Silently discard any invoices that
+                // could not be read sucessfully.
+                let inv = Invoice::load(number, config).ok()?;
+                let creditable = inv.amount_due(config).ok()?;
+
+                if creditable <= Money::ZERO {
+                    return None;
+                }
+
+                let description = format!(
+                    "#{} on {} for {}, {} {} not yet credited",
+                    inv.number, inv.date, inv.project_ref, creditable, inv.currency
+                );
+
+                let choice = SelectOption {
+                    value: (number, creditable),
+                    description,
+                };
+
+                Some(choice)
+            })
+            .collect();
+
+        if invoice_options.is_empty() {
+            bail!("All invoices have been fully credited!");
+        }
+
+        invoice_options.sort_by_key(|opt| u32::MAX - opt.value.0);
+
+        let invoice_choice = inquire::Select::new("Invoice number:", invoice_options)
+            .prompt()
+            .context("reading invoice number for credit note from user input")?;
+
+        let (invoice_num, creditable) = invoice_choice.value;
+
+        let number = Self::get_next_number(config).context("getting next credit note number")?;
+
+        print_header(&format!("Create credit note {}", number));
+
+        let chrono_date = inquire::DateSelect::new("Credit note date:")
+            .prompt()
+            .context("reading credit note date from user input")?;
+        // Convert `chrono::Date` to `time::Date`.
+        let date = DateString::try_new(chrono_date.to_string())
+            .context("parsing credit note DateString from user input")?;
+
+        let reason = inquire::Text::new("Reason:")
+            .with_validator(inquire::validator::ValueRequiredValidator::default())
+            .prompt()
+            .context("reading credit note reason from user input")?;
+
+        let amount = inquire::CustomType::<Money>::new("Amount credited (including tax):")
+            .with_default(creditable)
+            .with_help_message("Defaults to the rest of the invoice")
+            .prompt()
+            .context("reading amount credited from user input")?;
+
+        let mut credit_note = CreditNote {
+            number,
+            invoice_num,
+            date,
+            reason,
+            amount: Some(amount),
+        };
+
+        credit_note = credit_note
+            .edit_yaml()
+            .context("editing credit note yaml")?;
+        credit_note
+            .validate(config)
+            .context("validating credit note")?;
+
+        Ok(credit_note)
+    }
+
+    /// Check that the credit note number is still free (it may have been edited)
+    /// and that the credit note doesn't credit more than what is left of the invoice.
+    pub fn validate(&self, config: &Config) -> anyhow::Result<()> {
+        if Self::exists(self.number, config)? {
+            bail!("credit note {} already exists", self.number);
+        }
+
+        let invoice = find_invoice(self.invoice_num, config).context("finding invoice")?;
+        let total = invoice.total();
+        let previous: Vec<CreditNote> = Self::list_for_invoice(self.invoice_num, config)
+            .context("listing credit notes for invoice")?
+            .into_iter()
+            .filter(|credit_note| credit_note.number != self.number)
+            .collect();
+        let creditable = total - amount_credited(&previous, total);
+
+        match self.amount {
+            Some(amount) if amount <= Money::ZERO => {
+                bail!("credit note amount must be positive (found {})", amount)
+            }
+            Some(amount) if amount > creditable => bail!(
+                "credit note amount {} exceeds the {} left to credit on invoice {}",
+                amount,
+                creditable,
+                self.invoice_num
+            ),
+            None if creditable <= Money::ZERO => {
+                bail!(
+                    "invoice {} has already been fully credited",
+                    self.invoice_num
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}.yaml", self.number)
+    }
+
+    pub fn save(&self, config: &Config) -> anyhow::Result<()> {
+        let credit_notes_dir =
+            get_credit_notes_dir(config).context("getting credit notes directory")?;
+        let path = credit_notes_dir.join(self.filename());
+        let file = File::create(path).context("creating credit note yaml file")?;
+
+        serde_yaml::to_writer(file, self).context("serializing credit note yaml")?;
+
+        Ok(())
+    }
+
+    pub fn collect(self, config: &Config) -> anyhow::Result<FullCreditNote> {
+        let invoice = find_invoice(self.invoice_num, config).context("finding invoice")?;
+        let project = find_project(&invoice.project_ref, config).context("finding project")?;
+        let client = find_client(&project.client_ref, config).context("finding client")?;
+
+        let invoice_totals = invoice.totals();
+        let previous: Vec<CreditNote> = Self::list_for_invoice(self.invoice_num, config)
+            .context("listing credit notes for invoice")?
+            .into_iter()
+            .filter(|credit_note| credit_note.number < self.number)
+            .collect();
+        let previously_credited = amount_credited(&previous, invoice_totals.total);
+        let amount = self
+            .amount
+            .unwrap_or(invoice_totals.total - previously_credited);
+        let totals = CreditTotals::compute(&invoice_totals, amount);
+
+        // Later credit notes and payments don't change this credit note's balance
+        let date: Date = self
+            .date
+            .clone()
+            .try_into()
+            .context("parsing credit note date")?;
+        let mut receipts = Vec::new();
+        for receipt in
+            Receipt::list_for_invoice(self.invoice_num, config).context("loading receipts")?
+        {
+            let receipt_date: Date = receipt
+                .date
+                .clone()
+                .try_into()
+                .with_context(|| format!("parsing date of receipt {}", receipt.number()))?;
+            if receipt_date <= date {
+                receipts.push(receipt);
+            }
+        }
+        // Receipts without an amount paid off what was due before this credit note
+        let due_before = invoice_totals.total - previously_credited;
+        let balance = due_before - amount - amount_paid(&receipts, due_before);
+
+        let full_credit_note = FullCreditNote {
+            me: config.me.clone(),
+            credit_note: self,
+            invoice,
+            project,
+            client,
+            totals,
+            balance,
+        };
+        Ok(full_credit_note)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Template)]
+#[template(path = "credit_note.tex")]
+pub struct FullCreditNote {
+    pub me: Me,
+    pub credit_note: CreditNote,
+    pub invoice: Invoice,
+    pub project: Project,
+    pub client: Client,
+    /// The part of the invoice credited by this credit note.
+    pub totals: CreditTotals,
+    /// The amount still owed on the invoice after this and earlier credit notes,
+    /// and the payments received up to this credit note's date.
+    #[serde(serialize_with = "serialize_amount")]
+    pub balance: Money,
+}
+
+/// A full credit note with the values the built-in template computes,
+/// for rendering user-supplied templates.
+#[derive(Serialize)]
+pub struct CreditNoteTemplateContext<'a> {
+    #[serde(flatten)]
+    pub full_credit_note: &'a FullCreditNote,
+    /// The totals formatted as printed, e.g. `-29.50`.
+    pub formatted: FormattedTotals,
+    #[serde(serialize_with = "serialize_amount")]
+    pub refund: Money,
+    /// Whether nothing more is owed on the invoice, and nothing is refunded.
+    pub paid_in_full: bool,
+    /// Whether `refund` is to be paid back.
+    pub refund_due: bool,
+    pub has_latex_symbol: bool,
+}
+
+impl FullCreditNote {
+    pub fn filename(&self) -> String {
+        let name_no_whitespace = self.me.name.split_whitespace().collect::<Vec<_>>().join("");
+
+        format!(
+            "CreditNote_{}_{}.pdf",
+            name_no_whitespace, self.credit_note.number
+        )
+    }
+
+    /// The amount to pay back if the invoice had already been paid
+    /// beyond what is owed after this credit note.
+    pub fn refund(&self) -> Money {
+        -self.balance
+    }
+
+    /// The data available to user-supplied templates.
+    pub fn template_context(&self) -> CreditNoteTemplateContext<'_> {
+        CreditNoteTemplateContext {
+            full_credit_note: self,
+            formatted: self.totals.formatted(&self.credit_note.reason),
+            refund: self.refund(),
+            paid_in_full: self.balance.is_zero(),
+            refund_due: self.refund() > Money::ZERO,
+            has_latex_symbol: self.invoice.currency.has_latex_symbol(),
+        }
+    }
+
+    fn render_pdf(
+        &self,
+        pdf_output_path: impl AsRef<Path>,
+        config: &Config,
+        show_tex: bool,
+    ) -> anyhow::Result<()> {
+        let context = self.template_context();
+        let document = Document {
+            name: "credit_note",
+            latex: self,
+            context: &context,
+            me: &self.me,
+            project: &self.project,
+            client: &self.client,
+        };
+
+        document
+            .render_pdf(pdf_output_path.as_ref(), config, show_tex)
+            .context("rendering credit note PDF")
+    }
+
+    pub fn save_pdf(&self, config: &Config, show_tex: bool) -> anyhow::Result<PathBuf> {
+        let pdfs_dir = get_pdfs_dir(config).context("getting pdfs directory")?;
+        let path = pdfs_dir.join(self.filename());
+
+        self.render_pdf(&path, config, show_tex)
+            .context("generating credit note PDF")?;
+
+        Ok(path)
+    }
+
+    /// Write the reversal of (part of) the invoice transaction.
+    fn write_beancount_to<W: Write>(
+        &self,
+        config: &BeancountConfig,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        let placeholders = Placeholders {
+            client: self.client.name.to_string(),
+            project: self.project.name.to_string(),
+            year: self.credit_note.date.year(),
+            number: self.invoice.number,
+        };
+
+        let date = self.credit_note.date.to_beancount();
+        let narration = placeholders.fill(&config.credit_note_narration);
+        let income_account = placeholders
+            .fill_account(&config.income_account)
+            .context("getting income account")?;
+        let tax_account = placeholders
+            .fill_account(&config.tax_account)
+            .context("getting tax account")?;
+        // The receivable was booked in the year of the invoice
+        let invoice_placeholders = Placeholders {
+            year: self.invoice.date.year(),
+            ..placeholders.clone()
+        };
+        let receivable_account = invoice_placeholders
+            .fill_account(&config.receivable_account)
+            .context("getting receivable account")?;
+        let amount = |money: Money| {
+            Amount::builder()
+                .num(money.as_decimal())
+                .currency(self.invoice.currency.to_string().into())
+                .build()
+        };
+
+        let mut postings = Vec::new();
+        postings.push(
+            Posting::builder()
+                .account(income_account)
+                .units(amount(self.totals.before_tax).into())
+                .build(),
+        );
+        let tax = self.totals.tax();
+        if !tax.is_zero() {
+            postings.push(
+                Posting::builder()
+                    .account(tax_account)
+                    .units(amount(tax).into())
+                    .build(),
+            );
+        }
+        postings.push(
+            Posting::builder()
+                .account(receivable_account)
+                .units(amount(-self.totals.total).into())
+                .build(),
+        );
+
+        let txn = ConfiguredTransaction::new(config, &placeholders, date, narration, postings);
+        txn.write_to(writer)?;
+
+        Ok(())
+    }
+
+    pub fn write_beancount_to_string(&self, config: &BeancountConfig) -> anyhow::Result<String> {
+        let mut buf = Vec::<u8>::new();
+        self.write_beancount_to(config, &mut buf)?;
+        let string = String::from_utf8(buf)?;
+
+        Ok(string)
+    }
+
+    /// Save the reversing transaction and update the index,
+    /// unless beancount output is disabled.
+    pub fn save_beancount(&self, config: &Config) -> anyhow::Result<Option<PathBuf>> {
+        if !config.beancount.enabled {
+            return Ok(None);
+        }
+
+        let out_path = self.write_beancount(config)?;
+        rebuild_index(config).context("rebuilding beancount index")?;
+
+        Ok(Some(out_path))
+    }
+
+    /// Save the reversing transaction without updating the index.
+    pub fn write_beancount(&self, config: &Config) -> anyhow::Result<PathBuf> {
+        let beancount_dir = get_beancount_dir(config).context("getting beancount directory")?;
+        let filename = format!("CreditNote_{}.beancount", self.credit_note.number);
+        let out_path = beancount_dir.join(&filename);
+        let mut out_file = File::create(&out_path)?;
+
+        self.write_beancount_to(&config.beancount, &mut out_file)?;
+
+        Ok(out_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        config::BeancountConfig,
+        date::DateString,
+        money::{Money, Percentage},
+        test_util::create_full_test_invoice,
+    };
+
+    use super::{amount_credited, amounts_credited, CreditNote, CreditTotals, FullCreditNote};
+
+    fn credit_note(number: u32, amount: Option<&str>) -> CreditNote {
+        CreditNote {
+            number,
+            invoice_num: 17,
+            date: DateString::try_new("2023-01-20".to_owned()).unwrap(),
+            reason: "Hosting was not provided".to_owned(),
+            amount: amount.map(|amount| Money::from_str(amount).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_amount_credited() -> anyhow::Result<()> {
+        let total = Money::from_str("29.50")?;
+
+        let partial = [credit_note(1, Some("10"))];
+        assert_eq!(amount_credited(&partial, total), Money::from_str("10")?);
+
+        let rest = [credit_note(1, Some("10")), credit_note(2, None)];
+        assert_eq!(
+            amounts_credited(&rest, total),
+            [Money::from_str("10")?, Money::from_str("19.50")?]
+        );
+        assert_eq!(amount_credited(&rest, total), total);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_credit_totals() -> anyhow::Result<()> {
+        let mut full_invoice = create_full_test_invoice();
+        full_invoice.invoice.tax_rate = Some(Percentage::from_str("10")?);
+        let invoice_totals = full_invoice.invoice.totals();
+
+        let full = CreditTotals::compute(&invoice_totals, invoice_totals.total);
+        assert_eq!(full.before_tax, invoice_totals.before_tax());
+        assert_eq!(full.tax(), invoice_totals.tax());
+
+        let half = CreditTotals::compute(&invoice_totals, Money::from_str("16.22")?);
+        assert_eq!(half.tax(), Money::from_str("1.47")?);
+        assert_eq!(half.before_tax, Money::from_str("14.75")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_beancount() -> anyhow::Result<()> {
+        let mut full_invoice = create_full_test_invoice();
+        full_invoice.invoice.tax_rate = Some(Percentage::from_str("10")?);
+        let amount = Money::from_str("11.00")?;
+        let totals = CreditTotals::compute(&full_invoice.invoice.totals(), amount);
+        let full_credit_note = FullCreditNote {
+            me: full_invoice.me,
+            credit_note: credit_note(1, Some("11.00")),
+            invoice: full_invoice.invoice,
+            project: full_invoice.project,
+            client: full_invoice.client,
+            totals,
+            balance: Money::from_str("21.45")?,
+        };
+
+        let beancount_string =
+            full_credit_note.write_beancount_to_string(&BeancountConfig::default())?;
+
+        let expected = r#"2023-01-20 * "Credit Note for Invoice #17 - Test Project #1"
+	Income:TestClient1	10.00 USD
+	Liabilities:SalesTax	1.00 USD
+	Assets:AccountsReceivable	-11.00 USD
+"#;
+
+        assert!(beancount_string.contains(expected));
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 use serde::{Deserialize, Serialize};
-use time::{format_description::FormatItem, macros::format_description, Date};
+use time::{format_description::FormatItem, macros::format_description, Date, OffsetDateTime};
 
 /// A utility class for serializing / deserializing dates.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -17,6 +17,11 @@ impl DateString {
         Ok(Self(s))
     }
 
+    /// The date as written in YAML files, e.g. `2023-01-07`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
     pub fn to_beancount_owned(self) -> beancount_core::Date<'static> {
         beancount_core::Date::from_string_unchecked(self.to_string())
     }
@@ -24,6 +29,23 @@ impl DateString {
     pub fn to_beancount<'a>(&'a self) -> beancount_core::Date<'a> {
         beancount_core::Date::from_str_unchecked(&self.0)
     }
+
+    pub fn year(&self) -> i32 {
+        let date: Date = self
+            .clone()
+            .try_into()
+            .expect("DateString should be parseable w/ SERDE_FORMAT");
+
+        date.year()
+    }
+}
+
+impl FromStr for DateString {
+    type Err = time::error::Parse;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_new(s.to_owned())
+    }
 }
 
 impl From<Date> for DateString {
@@ -57,3 +79,11 @@ impl TryFrom<DateString> for Date {
         Ok(date)
     }
 }
+
+/// Get the current local date, falling back to UTC
+/// if the local offset can't be determined.
+pub fn today() -> Date {
+    OffsetDateTime::now_local()
+        .unwrap_or_else(|_| OffsetDateTime::now_utc())
+        .date()
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::money::{Money, Percentage};
+
+/// A discount, either a percentage of the discounted amount or a fixed amount.
+///
+/// Written in YAML as e.g. `10%` or `25.00`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiscountAmount {
+    Percent(Percentage),
+    Fixed(Money),
+}
+
+impl DiscountAmount {
+    /// The amount taken off of `price`, rounded to whole cents.
+    pub fn of(&self, price: Money) -> Money {
+        match self {
+            Self::Percent(percent) => percent.of(price).round_to_cents(),
+            Self::Fixed(amount) => amount.round_to_cents(),
+        }
+    }
+}
+
+impl Display for DiscountAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Percent(percent) => write!(f, "{}%", percent),
+            Self::Fixed(amount) => write!(f, "{}", amount),
+        }
+    }
+}
+
+impl FromStr for DiscountAmount {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.ends_with('%') {
+            s.parse().map(Self::Percent)
+        } else {
+            s.parse().map(Self::Fixed)
+        }
+    }
+}
+
+impl Serialize for DiscountAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Percent(_) => serializer.serialize_str(&self.to_string()),
+            Self::Fixed(amount) => amount.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DiscountAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DiscountAmountVisitor)
+    }
+}
+
+/// Accept plain numbers as fixed amounts, and strings ending in `%` as percentages.
+struct DiscountAmountVisitor;
+
+impl<'de> Visitor<'de> for DiscountAmountVisitor {
+    type Value = DiscountAmount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an amount or a percentage such as 10%")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Money::deserialize(de::value::I64Deserializer::new(v)).map(DiscountAmount::Fixed)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Money::deserialize(de::value::U64Deserializer::new(v)).map(DiscountAmount::Fixed)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Money::deserialize(de::value::F64Deserializer::new(v)).map(DiscountAmount::Fixed)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+/// A discount on a whole invoice.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Discount {
+    pub description: String,
+    pub amount: DiscountAmount,
+}
+
+impl Discount {
+    pub fn create_from_user_input() -> anyhow::Result<Option<Self>> {
+        let maybe_description = inquire::Text::new("Discount:")
+            .with_help_message("e.g. \"Loyalty discount\", or leave empty for no discount")
+            .prompt_skippable()
+            .context("reading discount from user input")?
+            // Convert Some("") to None
+            .filter(|line| !line.is_empty());
+
+        if let Some(description) = maybe_description {
+            let amount = inquire::CustomType::<DiscountAmount>::new("Discount amount:")
+                .with_help_message("A fixed amount, or a percentage such as 10%")
+                .prompt()
+                .context("reading discount amount from user input")?;
+
+            let discount = Discount {
+                description,
+                amount,
+            };
+
+            Ok(Some(discount))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl FromStr for Discount {
+    type Err = anyhow::Error;
+
+    /// Parse a discount of the form `description:amount`, e.g. `Loyalty:10%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((description, amount)) = s.rsplit_once(':') else {
+            bail!("discount {:?} must have the form description:amount", s);
+        };
+
+        let discount = Discount {
+            description: description.to_owned(),
+            amount: amount
+                .parse()
+                .with_context(|| format!("parsing discount amount {:?}", amount))?,
+        };
+
+        Ok(discount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::money::{Money, Percentage};
+
+    use super::{Discount, DiscountAmount};
+
+    #[test]
+    fn test_discount_yaml() -> anyhow::Result<()> {
+        let yaml = "- description: Loyalty\n  amount: 10%\n- description: Credit\n  amount: 25\n";
+        let expected = [
+            Discount {
+                description: "Loyalty".to_owned(),
+                amount: DiscountAmount::Percent(Percentage::from_str("10")?),
+            },
+            Discount {
+                description: "Credit".to_owned(),
+                amount: DiscountAmount::Fixed(Money::from_str("25")?),
+            },
+        ]
+        .to_vec();
+
+        let actual: Vec<Discount> = serde_yaml::from_str(yaml)?;
+        assert_eq!(actual, expected);
+
+        let reparsed: Vec<Discount> = serde_yaml::from_str(&serde_yaml::to_string(&actual)?)?;
+        assert_eq!(reparsed, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_discount_amount_of() -> anyhow::Result<()> {
+        let price = Money::from_str("19.99")?;
+
+        assert_eq!(
+            DiscountAmount::from_str("10%")?.of(price),
+            Money::from_str("2.00")?
+        );
+        assert_eq!(
+            DiscountAmount::from_str("5")?.of(price),
+            Money::from_str("5.00")?
+        );
+        assert_eq!("Loyalty:15%".parse::<Discount>()?.amount.to_string(), "15%");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use beancount_core::{Amount, Posting};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    beancount::{account_component, rebuild_index, ConfiguredTransaction, Placeholders},
+    client::Client,
+    config::{BeancountConfig, Config},
+    date::DateString,
+    id::Id,
+    invoice::LineItem,
+    money::{Currency, Money, Percentage, Quantity},
+    project::Project,
+    storage::{find_client, find_project, get_beancount_dir, get_expenses_dir},
+};
+
+/// Money spent on a project, which may be billed back to the client.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expense {
+    pub number: u32,
+    pub project_ref: Id,
+    pub date: DateString,
+    /// Category such as `Travel`, used for the `{category}` placeholder
+    /// of the beancount expense account.
+    pub category: String,
+    pub description: String,
+    pub amount: Money,
+    #[serde(default)]
+    pub currency: Currency,
+    /// Path to a scan or PDF of the receipt for this expense.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<PathBuf>,
+    /// Whether this expense is billed back to the client on an invoice.
+    #[serde(default)]
+    pub rebillable: bool,
+    /// Percentage added on top of the amount when billing the expense back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markup: Option<Percentage>,
+    /// Beancount account the expense was paid from, overriding the config's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paid_from: Option<String>,
+    /// The invoice this expense was billed on, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoice_num: Option<u32>,
+}
+
+impl Expense {
+    pub fn list(config: &Config) -> anyhow::Result<Vec<u32>> {
+        let expenses_dir = get_expenses_dir(config).context("getting expenses directory")?;
+
+        let numbers: Vec<u32> = expenses_dir
+            .read_dir()
+            .context("listing expense files")?
+            .filter_map(|entry_res| {
+                let entry = entry_res.ok()?;
+                let path = entry.path();
+                let stem = path.file_stem()?.to_string_lossy();
+                let number = stem.parse().ok()?;
+
+                Some(number)
+            })
+            .collect();
+
+        Ok(numbers)
+    }
+
+    /// Load all rebillable expenses of a project which weren't billed yet, in order.
+    pub fn list_unbilled(project: &Id, config: &Config) -> anyhow::Result<Vec<Self>> {
+        let mut numbers = Self::list(config).context("listing expenses")?;
+        numbers.sort();
+
+        let mut expenses = Vec::new();
+        for number in numbers {
+            let expense = Self::load(number, config)
+                .with_context(|| format!("loading expense {}", number))?;
+            if expense.project_ref == *project && expense.is_unbilled() {
+                expenses.push(expense);
+            }
+        }
+
+        Ok(expenses)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref()).context("opening expense file")?;
+        let expense: Expense = serde_yaml::from_reader(file).context("parsing expense yaml")?;
+
+        Ok(expense)
+    }
+
+    pub fn load(number: u32, config: &Config) -> anyhow::Result<Self> {
+        let expenses_dir = get_expenses_dir(config).context("getting expenses directory")?;
+        let filename = format!("{}.yaml", number);
+        let path = expenses_dir.join(filename);
+        let expense = Self::load_from_path(path).context("loading expense from file")?;
+
+        Ok(expense)
+    }
+
+    pub fn get_next_number(config: &Config) -> anyhow::Result<u32> {
+        let existing_numbers = Self::list(config).context("listing expenses")?;
+        let max = existing_numbers.iter().fold(0, |acc, &el| acc.max(el));
+
+        Ok(max + 1)
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}.yaml", self.number)
+    }
+
+    pub fn save(&self, config: &Config) -> anyhow::Result<()> {
+        let expenses_dir = get_expenses_dir(config).context("getting expenses directory")?;
+        let path = expenses_dir.join(self.filename());
+        let file = File::create(path).context("creating expense file")?;
+
+        serde_yaml::to_writer(file, self).context("serializing expense yaml")?;
+
+        Ok(())
+    }
+
+    pub fn is_unbilled(&self) -> bool {
+        self.rebillable && self.invoice_num.is_none()
+    }
+
+    /// The amount billed to the client, including the markup.
+    pub fn rebilled_amount(&self) -> Money {
+        let markup = self
+            .markup
+            .map(|markup| markup.of(self.amount))
+            .unwrap_or_default();
+
+        (self.amount + markup).round_to_cents()
+    }
+
+    /// Line items billing the given expenses back on an invoice in `currency`.
+    pub fn line_items(expenses: &[Self], currency: &Currency) -> anyhow::Result<Vec<LineItem>> {
+        expenses
+            .iter()
+            .map(|expense| {
+                if expense.currency != *currency {
+                    bail!(
+                        "expense {} is in {}, but the invoice is in {}",
+                        expense.number,
+                        expense.currency,
+                        currency
+                    );
+                }
+
+                Ok(LineItem {
+                    description: format!("{} ({})", expense.description, expense.date),
+                    quantity: Quantity::new(Decimal::ONE),
+                    unit_price: expense.rebilled_amount(),
+                    tax_rate: None,
+                    discount: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Mark the given expenses as billed on the given invoice,
+    /// unless they were billed in the meantime.
+    pub fn mark_billed(numbers: &[u32], invoice_num: u32, config: &Config) -> anyhow::Result<()> {
+        for &number in numbers {
+            let mut expense = Self::load(number, config)
+                .with_context(|| format!("loading expense {}", number))?;
+            if !expense.is_unbilled() {
+                continue;
+            }
+
+            expense.invoice_num = Some(invoice_num);
+            expense
+                .save(config)
+                .with_context(|| format!("saving expense {}", expense.number))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn collect(self, config: &Config) -> anyhow::Result<FullExpense> {
+        let project = find_project(&self.project_ref, config).context("finding project")?;
+        let client = find_client(&project.client_ref, config).context("finding client")?;
+
+        let full_expense = FullExpense {
+            expense: self,
+            project,
+            client,
+        };
+        Ok(full_expense)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FullExpense {
+    pub expense: Expense,
+    pub project: Project,
+    pub client: Client,
+}
+
+impl FullExpense {
+    /// Fill in the `{category}` and `{description}` placeholders,
+    /// which only exist for expenses.
+    fn fill_expense(&self, template: &str, category: &str) -> String {
+        template
+            .replace("{category}", category)
+            .replace("{description}", &self.expense.description)
+    }
+
+    fn write_beancount_to<W: Write>(
+        &self,
+        config: &BeancountConfig,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        let placeholders = Placeholders {
+            client: self.client.name.to_string(),
+            project: self.project.name.to_string(),
+            year: self.expense.date.year(),
+            number: self.expense.number,
+        };
+
+        let date = self.expense.date.to_beancount();
+        let narration = placeholders
+            .fill(&self.fill_expense(&config.expense_narration, &self.expense.category));
+        let expense_account = placeholders
+            .fill_account(&self.fill_expense(
+                &config.expense_account,
+                &account_component(&self.expense.category),
+            ))
+            .context("getting expense account")?;
+        let payment_account = placeholders
+            .fill_account(
+                self.expense
+                    .paid_from
+                    .as_ref()
+                    .unwrap_or(&config.expense_payment_account),
+            )
+            .context("getting expense payment account")?;
+        let amount = |money: Money| {
+            Amount::builder()
+                .num(money.as_decimal())
+                .currency(self.expense.currency.to_string().into())
+                .build()
+        };
+
+        let postings = vec![
+            Posting::builder()
+                .account(expense_account)
+                .units(amount(self.expense.amount).into())
+                .build(),
+            Posting::builder()
+                .account(payment_account)
+                .units(amount(-self.expense.amount).into())
+                .build(),
+        ];
+
+        let mut txn = ConfiguredTransaction::new(config, &placeholders, date, narration, postings);
+        if let Some(receipt) = &self.expense.receipt {
+            txn.metadata
+                .insert("document".to_owned(), receipt.display().to_string());
+        }
+        txn.write_to(writer)?;
+
+        Ok(())
+    }
+
+    pub fn write_beancount_to_string(&self, config: &BeancountConfig) -> anyhow::Result<String> {
+        let mut buf = Vec::<u8>::new();
+        self.write_beancount_to(config, &mut buf)?;
+        let string = String::from_utf8(buf)?;
+
+        Ok(string)
+    }
+
+    /// Save the expense transaction and update the index,
+    /// unless beancount output is disabled.
+    pub fn save_beancount(&self, config: &Config) -> anyhow::Result<Option<PathBuf>> {
+        if !config.beancount.enabled {
+            return Ok(None);
+        }
+
+        let out_path = self.write_beancount(config)?;
+        rebuild_index(config).context("rebuilding beancount index")?;
+
+        Ok(Some(out_path))
+    }
+
+    /// Save the expense transaction without updating the index.
+    pub fn write_beancount(&self, config: &Config) -> anyhow::Result<PathBuf> {
+        let beancount_dir = get_beancount_dir(config).context("getting beancount directory")?;
+        let filename = format!("Expense_{}.beancount", self.expense.number);
+        let out_path = beancount_dir.join(&filename);
+        let mut out_file = File::create(&out_path)?;
+
+        self.write_beancount_to(&config.beancount, &mut out_file)?;
+
+        Ok(out_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        config::BeancountConfig,
+        date::DateString,
+        money::{Currency, Money, Percentage},
+        test_util::create_full_test_invoice,
+    };
+
+    use super::{Expense, FullExpense};
+
+    fn expense(number: u32, amount: &str, markup: Option<&str>) -> Expense {
+        Expense {
+            number,
+            project_ref: "Test Project #1".to_owned().into(),
+            date: DateString::try_new("2023-01-05".to_owned()).unwrap(),
+            category: "Travel".to_owned(),
+            description: "Train to the client".to_owned(),
+            amount: Money::from_str(amount).unwrap(),
+            currency: Currency::default(),
+            receipt: None,
+            rebillable: true,
+            markup: markup.map(|markup| Percentage::from_str(markup).unwrap()),
+            paid_from: None,
+            invoice_num: None,
+        }
+    }
+
+    #[test]
+    fn test_line_items() -> anyhow::Result<()> {
+        let expenses = [expense(1, "40", None), expense(2, "12.35", Some("10%"))];
+
+        let items = Expense::line_items(&expenses, &Currency::default())?;
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[0].description,
+            "Train to the client (January 5, 2023)"
+        );
+        assert_eq!(items[0].total(), Money::from_str("40")?);
+        assert_eq!(items[1].total(), Money::from_str("13.59")?);
+
+        assert!(Expense::line_items(&expenses, &Currency::from_str("EUR")?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_beancount() -> anyhow::Result<()> {
+        let full_invoice = create_full_test_invoice();
+        let mut expense = expense(3, "40.00", None);
+        expense.receipt = Some("receipts/train.pdf".into());
+        let full_expense = FullExpense {
+            expense,
+            project: full_invoice.project,
+            client: full_invoice.client,
+        };
+
+        let beancount_string =
+            full_expense.write_beancount_to_string(&BeancountConfig::default())?;
+
+        let expected = r#"2023-01-05 * "Train to the client - Test Project #1"
+	document: "receipts/train.pdf"
+	Expenses:Travel	40.00 USD
+	Assets:Checking	-40.00 USD
+"#;
+
+        assert!(beancount_string.contains(expected));
+
+        Ok(())
+    }
+}
@@ -17,6 +17,11 @@ impl Id {
     pub fn new(s: String) -> Self {
         Self(s)
     }
+
+    /// Whether both IDs name the same file on a case-insensitive filesystem.
+    pub fn eq_ignore_case(&self, other: &Id) -> bool {
+        self.0.to_lowercase() == other.0.to_lowercase()
+    }
 }
 
 impl From<String> for Id {
@@ -1,41 +1,75 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
 use askama::Template;
-use beancount_core::{Account, AccountType, Amount, Posting, Transaction};
-use beancount_render::{BasicRenderer, Renderer};
-use rust_decimal::Decimal;
+use beancount_core::{Amount, Posting};
 use serde::{Deserialize, Serialize};
 use time::{Date, Duration};
 
 use crate::{
+    beancount::{rebuild_index, ConfiguredTransaction, Placeholders},
     cli::{print_header, NumberValidator, YamlValidator},
     client::Client,
     completion::PrefixAutocomplete,
-    config::Config,
-    date::DateString,
+    config::{BeancountConfig, Config},
+    credit_note::{amount_credited, CreditNote},
+    date::{today, DateString},
+    discount::{Discount, DiscountAmount},
+    expense::Expense,
     id::Id,
-    latex::{compile_latex, Asset, Latex},
-    me::Me,
-    price::PriceUSD,
+    latex::{Document, Latex},
+    me::{HtmlImages, Me},
+    money::{serialize_amount, Currency, Money, Percentage, Quantity},
     project::Project,
-    storage::{find_client, find_project, get_beancount_dir, get_invoices_dir, get_pdfs_dir},
+    receipt::{amount_paid, Receipt},
+    storage::{
+        find_client, find_project, get_beancount_dir, get_html_dir, get_invoices_dir, get_pdfs_dir,
+    },
+    timelog::{TimeEntry, TimeLog},
 };
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct LineItem {
     pub description: String,
-    pub quantity: f32,
-    pub unit_price: PriceUSD,
+    pub quantity: Quantity,
+    pub unit_price: Money,
+    /// Tax rate for this item, overriding the invoice's tax rate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax_rate: Option<Percentage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount: Option<DiscountAmount>,
 }
 
 impl LineItem {
-    pub fn create_from_user_input() -> anyhow::Result<Option<Self>> {
+    /// The price of this line item before any discount, rounded to whole cents.
+    pub fn subtotal(&self) -> Money {
+        (self.unit_price * self.quantity).round_to_cents()
+    }
+
+    /// The amount taken off of this line item's price by its discount.
+    pub fn discount(&self) -> Money {
+        self.discount
+            .map(|discount| discount.of(self.subtotal()))
+            .unwrap_or_default()
+    }
+
+    /// The price of this line item after its discount.
+    pub fn total(&self) -> Money {
+        self.subtotal() - self.discount()
+    }
+
+    /// Prompt for a line item, pre-filling the unit price with `default_unit_price`.
+    /// Returns `None` if the description is left empty.
+    pub fn create_from_user_input(
+        default_unit_price: Option<Money>,
+    ) -> anyhow::Result<Option<Self>> {
         let maybe_description = inquire::Text::new("Line item:")
             .prompt_skippable()
             .context("reading line item from user input")?
@@ -43,10 +77,14 @@ impl LineItem {
             .filter(|line| !line.is_empty());
 
         if let Some(description) = maybe_description {
-            let quantity = inquire::CustomType::<f32>::new("Quantity:")
+            let quantity = inquire::CustomType::<Quantity>::new("Quantity:")
                 .prompt()
                 .context("reading quantity from user input")?;
-            let unit_price = inquire::CustomType::<PriceUSD>::new("Unit Price:")
+            let mut unit_price_prompt = inquire::CustomType::<Money>::new("Unit Price:");
+            if let Some(default_unit_price) = default_unit_price {
+                unit_price_prompt = unit_price_prompt.with_default(default_unit_price);
+            }
+            let unit_price = unit_price_prompt
                 .prompt()
                 .context("reading unit price from user input")?;
 
@@ -54,6 +92,8 @@ impl LineItem {
                 description,
                 quantity,
                 unit_price,
+                tax_rate: None,
+                discount: None,
             };
 
             Ok(Some(line_item))
@@ -63,6 +103,364 @@ impl LineItem {
     }
 }
 
+impl FromStr for LineItem {
+    type Err = anyhow::Error;
+
+    /// Parse a line item of the form `description:quantity:unit_price`.
+    /// The description may itself contain colons.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.rsplitn(3, ':');
+        let (Some(unit_price), Some(quantity), Some(description)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!(
+                "line item {:?} must have the form description:quantity:unit_price",
+                s
+            );
+        };
+
+        let line_item = LineItem {
+            description: description.to_owned(),
+            quantity: quantity
+                .trim()
+                .parse()
+                .with_context(|| format!("parsing quantity {:?}", quantity))?,
+            unit_price: unit_price
+                .trim()
+                .parse()
+                .with_context(|| format!("parsing unit price {:?}", unit_price))?,
+            tax_rate: None,
+            discount: None,
+        };
+
+        Ok(line_item)
+    }
+}
+
+/// Tax owed on all line items sharing a tax rate.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaxLine {
+    pub rate: Percentage,
+    #[serde(serialize_with = "serialize_amount")]
+    pub taxable: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub tax: Money,
+}
+
+/// An amount taken off of an invoice, either for a single line item
+/// or for the whole invoice.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DiscountLine {
+    pub description: String,
+    /// The (positive) amount of the discount.
+    #[serde(serialize_with = "serialize_amount")]
+    pub amount: Money,
+}
+
+/// Totals of a list of line items, matching the arithmetic
+/// that the LaTeX invoice class performs when rendering.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Totals {
+    /// Sum of all line item prices, before discounts and tax.
+    #[serde(serialize_with = "serialize_amount")]
+    pub subtotal: Money,
+    /// Line item discounts followed by whole-invoice discounts.
+    pub discounts: Vec<DiscountLine>,
+    /// Tax for each distinct tax rate, in increasing order of rate.
+    pub taxes: Vec<TaxLine>,
+    #[serde(serialize_with = "serialize_amount")]
+    pub total: Money,
+}
+
+impl Totals {
+    /// Compute totals for the given items and discounts.
+    ///
+    /// Items which don't specify their own tax rate are taxed at `default_tax_rate`.
+    /// Whole-invoice discounts are taken off of the subtotal after line item discounts,
+    /// and reduce the amount taxed at each rate in proportion to that rate's share of the items.
+    pub fn compute(
+        items: &[LineItem],
+        discounts: &[Discount],
+        default_tax_rate: Option<Percentage>,
+    ) -> Self {
+        let subtotal: Money = items.iter().map(LineItem::subtotal).sum();
+
+        let mut discount_lines: Vec<DiscountLine> = items
+            .iter()
+            .filter_map(|item| {
+                let amount = item.discount?;
+                let description = format!("{} ({})", item.description, amount);
+
+                Some(DiscountLine {
+                    description,
+                    amount: item.discount(),
+                })
+            })
+            .collect();
+
+        let mut taxable_by_rate = BTreeMap::<Percentage, Money>::new();
+        for item in items {
+            if let Some(rate) = item.tax_rate.or(default_tax_rate) {
+                let taxable = taxable_by_rate.entry(rate).or_default();
+                *taxable = *taxable + item.total();
+            }
+        }
+
+        let items_total: Money = items.iter().map(LineItem::total).sum();
+        let mut invoice_discount = Money::ZERO;
+        for discount in discounts {
+            let amount = discount.amount.of(items_total);
+            invoice_discount = invoice_discount + amount;
+
+            discount_lines.push(DiscountLine {
+                description: discount.description.clone(),
+                amount,
+            });
+        }
+
+        // Spread whole-invoice discounts over the tax rates by each rate's share of the items
+        if !items_total.is_zero() {
+            for taxable in taxable_by_rate.values_mut() {
+                let share = Money::new(
+                    invoice_discount.as_decimal() * taxable.as_decimal() / items_total.as_decimal(),
+                )
+                .round_to_cents();
+                *taxable = *taxable - share;
+            }
+        }
+
+        let taxes: Vec<TaxLine> = taxable_by_rate
+            .into_iter()
+            .map(|(rate, taxable)| TaxLine {
+                rate,
+                taxable,
+                tax: rate.of(taxable).round_to_cents(),
+            })
+            .collect();
+
+        let discount: Money = discount_lines.iter().map(|line| line.amount).sum();
+        let tax: Money = taxes.iter().map(|line| line.tax).sum();
+        let total = subtotal - discount + tax;
+
+        Self {
+            subtotal,
+            discounts: discount_lines,
+            taxes,
+            total,
+        }
+    }
+
+    pub fn discount(&self) -> Money {
+        self.discounts.iter().map(|line| line.amount).sum()
+    }
+
+    /// The subtotal after all discounts.
+    pub fn before_tax(&self) -> Money {
+        self.subtotal - self.discount()
+    }
+
+    pub fn tax(&self) -> Money {
+        self.taxes.iter().map(|line| line.tax).sum()
+    }
+}
+
+/// A row of the items table, formatted the way the built-in templates print it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FormattedLine {
+    pub description: String,
+    pub quantity: String,
+    pub unit_price: String,
+    pub amount: String,
+}
+
+impl FormattedLine {
+    fn item(item: &LineItem) -> Self {
+        Self {
+            description: item.description.clone(),
+            quantity: item.quantity.to_string(),
+            unit_price: item.unit_price.to_string(),
+            amount: item.subtotal().to_string(),
+        }
+    }
+
+    fn discount(discount: &DiscountLine) -> Self {
+        let amount = format!("-{}", discount.amount);
+
+        Self {
+            description: discount.description.clone(),
+            quantity: "1".to_owned(),
+            unit_price: amount.clone(),
+            amount,
+        }
+    }
+}
+
+/// Tax at one rate, formatted the way the built-in templates print it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FormattedTax {
+    pub rate: String,
+    pub tax: String,
+}
+
+/// The items table of an invoice, quote or credit note with every number already formatted,
+/// for Typst templates, which would otherwise do the arithmetic with floats.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FormattedTotals {
+    pub items: Vec<FormattedLine>,
+    pub expenses: Vec<FormattedLine>,
+    pub discounts: Vec<FormattedLine>,
+    pub subtotal: String,
+    pub taxes: Vec<FormattedTax>,
+    pub total: String,
+}
+
+impl FormattedTotals {
+    pub fn new(items: &[LineItem], expenses: &[LineItem], totals: &Totals) -> Self {
+        Self {
+            items: items.iter().map(FormattedLine::item).collect(),
+            expenses: expenses.iter().map(FormattedLine::item).collect(),
+            discounts: totals
+                .discounts
+                .iter()
+                .map(FormattedLine::discount)
+                .collect(),
+            subtotal: totals.subtotal.to_string(),
+            taxes: totals
+                .taxes
+                .iter()
+                .map(|tax| FormattedTax {
+                    rate: tax.rate.to_string(),
+                    tax: tax.tax.to_string(),
+                })
+                .collect(),
+            total: totals.total.to_string(),
+        }
+    }
+}
+
+/// Invoice fields which can be given on the command line
+/// to create an invoice without any interactive prompts.
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct InvoiceArgs {
+    /// Invoice number (defaults to the next available number)
+    #[arg(long)]
+    pub number: Option<u32>,
+    /// Project name
+    #[arg(long)]
+    pub project: Option<Id>,
+    /// Invoice date as YYYY-MM-DD (defaults to today)
+    #[arg(long)]
+    pub date: Option<DateString>,
+    /// Number of days after the invoice date that payment is due
+    /// (defaults to the project's or client's, or else 7)
+    #[arg(long)]
+    pub days_to_pay: Option<u16>,
+    /// Currency code (defaults to the project's or client's currency)
+    #[arg(long)]
+    pub currency: Option<Currency>,
+    /// Tax rate in percent (defaults to the client's or configured tax rate)
+    #[arg(long)]
+    pub tax_rate: Option<Percentage>,
+    /// Line item as "description:quantity:unit_price" (repeatable)
+    #[arg(long = "item", value_name = "ITEM")]
+    pub items: Vec<LineItem>,
+    /// Whole-invoice discount as "description:amount", where the amount
+    /// may be a percentage such as 10% (repeatable)
+    #[arg(long = "discount", value_name = "DISCOUNT")]
+    pub discounts: Vec<Discount>,
+    /// Terms and conditions (defaults to the project's or client's)
+    #[arg(long)]
+    pub conditions: Option<String>,
+    /// Add the project's unbilled time entries as line items,
+    /// and mark them as billed on this invoice
+    #[arg(long)]
+    pub bill_time: bool,
+    /// Hourly rate for time entries without their own rate
+    /// (defaults to the project's or client's)
+    #[arg(long, requires = "bill_time")]
+    pub hourly_rate: Option<Money>,
+    /// Add the project's unbilled rebillable expenses,
+    /// and mark them as billed on this invoice
+    #[arg(long)]
+    pub bill_expenses: bool,
+}
+
+impl InvoiceArgs {
+    /// Whether enough information was given on the command line
+    /// to skip the interactive prompts: a project and something to bill.
+    pub fn is_complete(&self) -> bool {
+        self.project.is_some() && (!self.items.is_empty() || self.bill_time || self.bill_expenses)
+    }
+}
+
+/// Which of a project's unbilled time entries and expenses were added to an invoice,
+/// so that they can be marked as billed once the invoice is saved.
+#[derive(Clone, Debug, Default)]
+pub struct Billed {
+    /// The billed time entries, each with the line item it was billed as.
+    pub time_entries: Vec<(TimeEntry, LineItem)>,
+    /// Numbers of the billed expenses, each with the line item it was billed as.
+    pub expenses: Vec<(u32, LineItem)>,
+}
+
+impl Billed {
+    /// Forget the time entries and expenses which an edited invoice no longer bills:
+    /// all of them if it was moved away from `project`,
+    /// and otherwise those whose line item was removed.
+    pub fn retain_invoiced(&mut self, project: &Id, invoice: &Invoice) {
+        if invoice.project_ref != *project {
+            *self = Self::default();
+            return;
+        }
+
+        retain_matching(&mut self.time_entries, &invoice.items);
+        retain_matching(&mut self.expenses, &invoice.expenses);
+    }
+
+    pub fn mark(&self, invoice: &Invoice, config: &Config) -> anyhow::Result<()> {
+        if !self.time_entries.is_empty() {
+            let entries: Vec<TimeEntry> = self
+                .time_entries
+                .iter()
+                .map(|(entry, _)| entry.clone())
+                .collect();
+            let mut time_log =
+                TimeLog::load(&invoice.project_ref, config).context("loading time log")?;
+            time_log.mark_billed(&entries, invoice.number);
+            time_log
+                .save(&invoice.project_ref, config)
+                .context("saving billed time log")?;
+        }
+
+        if !self.expenses.is_empty() {
+            let numbers: Vec<u32> = self.expenses.iter().map(|(number, _)| *number).collect();
+            Expense::mark_billed(&numbers, invoice.number, config)
+                .context("marking expenses as billed")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keep the billed things which still have a line item with the same description
+/// and quantity, matching each line item at most once.
+fn retain_matching<T>(billed: &mut Vec<(T, LineItem)>, items: &[LineItem]) {
+    let mut unmatched: Vec<&LineItem> = items.iter().collect();
+    billed.retain(|(_, billed_item)| {
+        let position = unmatched.iter().position(|item| {
+            item.description == billed_item.description && item.quantity == billed_item.quantity
+        });
+
+        match position {
+            Some(index) => {
+                unmatched.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    });
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Invoice {
@@ -70,7 +468,17 @@ pub struct Invoice {
     pub project_ref: Id,
     pub date: DateString,
     pub due_date: DateString,
+    #[serde(default)]
+    pub currency: Currency,
+    /// Tax rate (in percent) for line items which don't specify their own.
+    #[serde(default)]
+    pub tax_rate: Option<Percentage>,
     pub items: Vec<LineItem>,
+    /// Rebilled expenses, listed in their own section.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expenses: Vec<LineItem>,
+    #[serde(default)]
+    pub discounts: Vec<Discount>,
     pub conditions: Option<String>,
 }
 
@@ -143,7 +551,9 @@ impl Invoice {
         Ok(parsed)
     }
 
-    pub fn create_from_user_input(config: &Config) -> anyhow::Result<Self> {
+    /// Create an invoice from prompts, also returning which of the project's
+    /// unbilled time entries and expenses were added to it.
+    pub fn create_from_user_input(config: &Config) -> anyhow::Result<(Self, Billed)> {
         let required_validator = inquire::validator::ValueRequiredValidator::default();
         let number_validator = NumberValidator::new();
 
@@ -158,7 +568,9 @@ impl Invoice {
             .parse()
             .context("parsing invoice number")?;
 
-        let mut invoice = if Self::exists(invoice_number, config)? {
+        let mut billed = Billed::default();
+        let existing = Self::exists(invoice_number, config)?;
+        let mut invoice = if existing {
             // Load invoice if number already exists
             Self::load(invoice_number, config)?
         } else {
@@ -167,6 +579,7 @@ impl Invoice {
 
             let project_name = Project::get_or_create_from_user_input(config)
                 .context("getting or creating project")?;
+            let project = find_project(&project_name, config).context("finding project")?;
 
             let chrono_date = inquire::DateSelect::new("Invoice date:")
                 .prompt()
@@ -179,8 +592,11 @@ impl Invoice {
                 .try_into()
                 .context("parsing invoice Date from user input")?;
 
+            let default_days_to_pay = project
+                .default_days_to_pay(config)
+                .context("getting default days to pay")?;
             let days_to_pay = inquire::CustomType::<u16>::new("Days to pay:")
-                .with_default(7)
+                .with_default(default_days_to_pay)
                 .prompt()
                 .context("reading days-to-pay from user input")?;
 
@@ -188,16 +604,113 @@ impl Invoice {
             let due_date_string =
                 DateString::try_from(due_date).context("converting due date to DateString")?;
 
+            let default_currency = project
+                .default_currency(config)
+                .context("getting default currency")?;
+            let currency = inquire::CustomType::<Currency>::new("Currency:")
+                .with_default(default_currency)
+                .prompt()
+                .context("reading currency from user input")?;
+
+            let default_tax_rate = project
+                .default_tax_rate(config)
+                .context("getting default tax rate")?
+                .map(|rate| rate.to_string())
+                .unwrap_or_default();
+            let tax_rate = inquire::Text::new("Tax rate (%):")
+                .with_initial_value(&default_tax_rate)
+                .with_help_message("Leave empty for no tax")
+                .prompt_skippable()
+                .context("reading tax rate from user input")?
+                // Convert Some("") to None
+                .filter(|line| !line.is_empty())
+                .map(|line| line.parse::<Percentage>())
+                .transpose()
+                .context("parsing tax rate")?;
+
+            let default_hourly_rate = project
+                .default_hourly_rate(config)
+                .context("getting default hourly rate")?;
+
             let mut items = Vec::new();
 
-            while let Some(item) =
-                LineItem::create_from_user_input().context("creating line item from user input")?
+            let time_log = TimeLog::load(&project_name, config).context("loading time log")?;
+            let unbilled_count = time_log.unbilled().count();
+            let mut bill_time = false;
+            if unbilled_count > 0 {
+                bill_time = inquire::Confirm::new(&format!(
+                    "Bill {} unbilled time entries?",
+                    unbilled_count
+                ))
+                .with_default(true)
+                .prompt()
+                .context("reading whether to bill time entries from user input")?;
+            }
+            if bill_time {
+                let hourly_rate = if time_log.unbilled().any(|entry| entry.rate.is_none()) {
+                    let mut rate_prompt = inquire::CustomType::<Money>::new("Hourly rate:");
+                    if let Some(default_hourly_rate) = default_hourly_rate {
+                        rate_prompt = rate_prompt.with_default(default_hourly_rate);
+                    }
+                    let rate = rate_prompt
+                        .prompt()
+                        .context("reading hourly rate from user input")?;
+                    Some(rate)
+                } else {
+                    None
+                };
+                billed.time_entries = time_log
+                    .unbilled_items(hourly_rate)
+                    .context("billing time entries")?;
+                items.extend(billed.time_entries.iter().map(|(_, item)| item.clone()));
+            }
+
+            while let Some(item) = LineItem::create_from_user_input(default_hourly_rate)
+                .context("creating line item from user input")?
             {
                 items.push(item);
             }
 
+            let unbilled_expenses =
+                Expense::list_unbilled(&project_name, config).context("listing expenses")?;
+            let mut bill_expenses = false;
+            if !unbilled_expenses.is_empty() {
+                bill_expenses = inquire::Confirm::new(&format!(
+                    "Bill {} unbilled expenses?",
+                    unbilled_expenses.len()
+                ))
+                .with_default(true)
+                .prompt()
+                .context("reading whether to bill expenses from user input")?;
+            }
+            let expenses = if bill_expenses {
+                let expenses = Expense::line_items(&unbilled_expenses, &currency)
+                    .context("billing expenses")?;
+                billed.expenses = unbilled_expenses
+                    .iter()
+                    .map(|expense| expense.number)
+                    .zip(expenses.iter().cloned())
+                    .collect();
+                expenses
+            } else {
+                Vec::new()
+            };
+
+            let mut discounts = Vec::new();
+
+            while let Some(discount) =
+                Discount::create_from_user_input().context("creating discount from user input")?
+            {
+                discounts.push(discount);
+            }
+
+            let default_conditions = project
+                .default_conditions(config)
+                .context("getting default conditions")?
+                .unwrap_or_default();
             let conditions = inquire::Text::new("Terms and conditions:")
                 .with_placeholder("Please be nice and pay me on time.")
+                .with_initial_value(&default_conditions)
                 .prompt_skippable()
                 .context("reading conditions from user input")?
                 // Convert Some("") to None
@@ -208,14 +721,244 @@ impl Invoice {
                 project_ref: project_name,
                 date: invoice_date_string,
                 due_date: due_date_string,
+                currency,
+                tax_rate,
                 items,
+                expenses,
+                discounts,
                 conditions,
             }
         };
 
+        let project = invoice.project_ref.clone();
         invoice = invoice.edit_yaml().context("editing invoice yaml")?;
+        billed.retain_invoiced(&project, &invoice);
 
-        Ok(invoice)
+        if existing && invoice.number == invoice_number {
+            // Saving an edited invoice replaces the one it was loaded from
+            invoice.validate_contents(config)
+        } else {
+            invoice.validate(config)
+        }
+        .context("validating invoice")?;
+
+        Ok((invoice, billed))
+    }
+
+    /// Create an invoice from command line arguments, without prompting.
+    pub fn create_from_args(args: InvoiceArgs, config: &Config) -> anyhow::Result<(Self, Billed)> {
+        let project_ref = args
+            .project
+            .ok_or(anyhow!("a project is required to create an invoice"))?;
+
+        let number = match args.number {
+            Some(number) => number,
+            None => Self::get_next_number(config).context("getting next invoice number")?,
+        };
+
+        let invoice_date = match args.date {
+            Some(date_string) => date_string
+                .try_into()
+                .context("parsing invoice date from arguments")?,
+            None => today(),
+        };
+        let project = find_project(&project_ref, config)
+            .with_context(|| format!("finding project {:?}", project_ref))?;
+
+        let days_to_pay = match args.days_to_pay {
+            Some(days_to_pay) => days_to_pay,
+            None => project
+                .default_days_to_pay(config)
+                .context("getting default days to pay")?,
+        };
+        let due_date = invoice_date + Duration::days(days_to_pay.into());
+
+        let currency = match args.currency {
+            Some(currency) => currency,
+            None => project
+                .default_currency(config)
+                .context("getting default currency")?,
+        };
+
+        let tax_rate = match args.tax_rate {
+            Some(tax_rate) => Some(tax_rate),
+            None => project
+                .default_tax_rate(config)
+                .context("getting default tax rate")?,
+        };
+
+        let conditions = match args.conditions {
+            Some(conditions) => Some(conditions),
+            None => project
+                .default_conditions(config)
+                .context("getting default conditions")?,
+        };
+
+        let mut billed = Billed::default();
+
+        let mut items = Vec::new();
+        if args.bill_time {
+            let time_log = TimeLog::load(&project_ref, config).context("loading time log")?;
+            let hourly_rate = match args.hourly_rate {
+                Some(hourly_rate) => Some(hourly_rate),
+                None => project
+                    .default_hourly_rate(config)
+                    .context("getting default hourly rate")?,
+            };
+            billed.time_entries = time_log
+                .unbilled_items(hourly_rate)
+                .context("billing time entries")?;
+            items.extend(billed.time_entries.iter().map(|(_, item)| item.clone()));
+        }
+        items.extend(args.items);
+
+        let mut expenses = Vec::new();
+        if args.bill_expenses {
+            let unbilled_expenses =
+                Expense::list_unbilled(&project_ref, config).context("listing expenses")?;
+            expenses =
+                Expense::line_items(&unbilled_expenses, &currency).context("billing expenses")?;
+            billed.expenses = unbilled_expenses
+                .iter()
+                .map(|expense| expense.number)
+                .zip(expenses.iter().cloned())
+                .collect();
+        }
+
+        let invoice = Invoice {
+            number,
+            project_ref,
+            date: invoice_date.into(),
+            due_date: due_date.into(),
+            currency,
+            tax_rate,
+            items,
+            expenses,
+            discounts: args.discounts,
+            conditions,
+        };
+
+        Ok((invoice, billed))
+    }
+
+    /// Check that a new invoice is consistent with the stored data before saving it.
+    pub fn validate(&self, config: &Config) -> anyhow::Result<()> {
+        if Self::exists(self.number, config)? {
+            bail!("invoice {} already exists", self.number);
+        }
+
+        self.validate_contents(config)
+    }
+
+    /// Check everything [`Invoice::validate`] does except that the number is new,
+    /// for an existing invoice which was edited.
+    fn validate_contents(&self, config: &Config) -> anyhow::Result<()> {
+        find_project(&self.project_ref, config)
+            .with_context(|| format!("finding project {:?}", self.project_ref))?;
+
+        let date: Date = self
+            .date
+            .clone()
+            .try_into()
+            .context("parsing invoice date")?;
+        let due_date: Date = self
+            .due_date
+            .clone()
+            .try_into()
+            .context("parsing invoice due date")?;
+        if due_date < date {
+            bail!(
+                "due date {} is before invoice date {}",
+                self.due_date,
+                self.date
+            );
+        }
+
+        if self.items.is_empty() && self.expenses.is_empty() {
+            bail!("invoice {} has no line items", self.number);
+        }
+
+        let totals = self.totals();
+        if totals.discount() > totals.subtotal {
+            bail!(
+                "discounts of {} exceed the subtotal of {}",
+                totals.discount(),
+                totals.subtotal
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Totals of all line items and rebilled expenses.
+    pub fn totals(&self) -> Totals {
+        let items: Vec<LineItem> = self.items.iter().chain(&self.expenses).cloned().collect();
+
+        Totals::compute(&items, &self.discounts, self.tax_rate)
+    }
+
+    /// The amount due, including tax.
+    pub fn total(&self) -> Money {
+        self.totals().total
+    }
+
+    /// The amount owed after all credit notes, including tax.
+    pub fn amount_due(&self, config: &Config) -> anyhow::Result<Money> {
+        let credit_notes =
+            CreditNote::list_for_invoice(self.number, config).context("loading credit notes")?;
+        let total = self.total();
+        let amount_due = total - amount_credited(&credit_notes, total);
+
+        Ok(amount_due)
+    }
+
+    /// The amount owed after the credit notes dated on or before `as_of`, including tax.
+    pub fn amount_due_as_of(&self, as_of: Date, config: &Config) -> anyhow::Result<Money> {
+        let mut credit_notes = Vec::new();
+        for credit_note in
+            CreditNote::list_for_invoice(self.number, config).context("loading credit notes")?
+        {
+            let date: Date =
+                credit_note.date.clone().try_into().with_context(|| {
+                    format!("parsing date of credit note {}", credit_note.number)
+                })?;
+            if date <= as_of {
+                credit_notes.push(credit_note);
+            }
+        }
+        let total = self.total();
+        let amount_due = total - amount_credited(&credit_notes, total);
+
+        Ok(amount_due)
+    }
+
+    /// The amount still owed after all credit notes and payments received so far.
+    pub fn outstanding_balance(&self, config: &Config) -> anyhow::Result<Money> {
+        let receipts =
+            Receipt::list_for_invoice(self.number, config).context("loading receipts")?;
+        let amount_due = self.amount_due(config)?;
+        let balance = amount_due - amount_paid(&receipts, amount_due);
+
+        Ok(balance)
+    }
+
+    /// The amount owed after the credit notes and payments dated on or before `as_of`.
+    pub fn outstanding_balance_as_of(&self, as_of: Date, config: &Config) -> anyhow::Result<Money> {
+        let mut receipts = Vec::new();
+        for receipt in Receipt::list_for_invoice(self.number, config).context("loading receipts")? {
+            let date: Date = receipt
+                .date
+                .clone()
+                .try_into()
+                .with_context(|| format!("parsing date of receipt {}", receipt.number()))?;
+            if date <= as_of {
+                receipts.push(receipt);
+            }
+        }
+        let amount_due = self.amount_due_as_of(as_of, config)?;
+        let balance = amount_due - amount_paid(&receipts, amount_due);
+
+        Ok(balance)
     }
 
     pub fn filename(&self) -> String {
@@ -256,109 +999,208 @@ pub struct FullInvoice {
     pub client: Client,
 }
 
+/// A full invoice with the values the built-in template computes,
+/// for rendering user-supplied templates.
+#[derive(Serialize)]
+pub struct InvoiceTemplateContext<'a> {
+    #[serde(flatten)]
+    pub full_invoice: &'a FullInvoice,
+    pub totals: Totals,
+    /// The totals formatted as printed, e.g. `29.50`.
+    pub formatted: FormattedTotals,
+    pub has_latex_symbol: bool,
+}
+
+/// An invoice as a single HTML page with inline CSS.
+#[derive(Template)]
+#[template(path = "invoice.html")]
+pub struct InvoiceHtml<'a> {
+    pub me: &'a Me,
+    pub images: HtmlImages,
+    pub invoice: &'a Invoice,
+    pub project: &'a Project,
+    pub client: &'a Client,
+    pub totals: Totals,
+}
+
 impl FullInvoice {
-    pub fn filename(&self) -> String {
+    fn file_stem(&self) -> String {
         let name_no_whitespace = self.me.name.split_whitespace().collect::<Vec<_>>().join("");
 
-        format!("Invoice_{}_{}.pdf", name_no_whitespace, self.invoice.number)
+        format!("Invoice_{}_{}", name_no_whitespace, self.invoice.number)
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}.pdf", self.file_stem())
+    }
+
+    pub fn html_filename(&self) -> String {
+        format!("{}.html", self.file_stem())
     }
 
-    fn render_pdf(&self, pdf_output_path: impl AsRef<Path>, show_tex: bool) -> anyhow::Result<()> {
-        let rendered_tex = Template::render(self).context("rendering invoice template")?;
+    /// The data available to user-supplied templates.
+    pub fn template_context(&self) -> InvoiceTemplateContext<'_> {
+        let totals = self.invoice.totals();
 
-        if show_tex {
-            println!("Final LaTeX:\n\n{}", &rendered_tex);
+        InvoiceTemplateContext {
+            full_invoice: self,
+            formatted: FormattedTotals::new(&self.invoice.items, &self.invoice.expenses, &totals),
+            totals,
+            has_latex_symbol: self.invoice.currency.has_latex_symbol(),
         }
+    }
 
-        let invoice_class = Asset {
-            data: include_bytes!("../assets/CSMinimalInvoice.cls").to_vec(),
-            filename: "CSMinimalInvoice.cls".to_owned(),
+    fn render_pdf(
+        &self,
+        pdf_output_path: impl AsRef<Path>,
+        config: &Config,
+        show_tex: bool,
+    ) -> anyhow::Result<()> {
+        let context = self.template_context();
+        let document = Document {
+            name: "invoice",
+            latex: self,
+            context: &context,
+            me: &self.me,
+            project: &self.project,
+            client: &self.client,
         };
-        let assets = &[invoice_class];
-        compile_latex(&rendered_tex, pdf_output_path.as_ref(), assets)
-            .context("compiling invoice LaTeX to PDF")?;
 
-        Ok(())
+        document
+            .render_pdf(pdf_output_path.as_ref(), config, show_tex)
+            .context("rendering invoice PDF")
     }
 
     pub fn save_pdf(&self, config: &Config, show_tex: bool) -> anyhow::Result<PathBuf> {
         let pdfs_dir = get_pdfs_dir(config).context("getting PDF directory")?;
         let path = pdfs_dir.join(self.filename());
 
-        self.render_pdf(&path, show_tex)
+        self.render_pdf(&path, config, show_tex)
             .context("generating invoice PDF")?;
 
         Ok(path)
     }
 
-    fn write_beancount_to<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
-        let total_cost: f32 = self
-            .invoice
-            .items
-            .iter()
-            .map(|item| item.quantity * item.unit_price.as_f32())
-            .sum();
-        // TODO: proper decimal math
-        let total_cost_str = format!("{:.2}", total_cost);
-        let total_cost_decimal = Decimal::from_str_exact(&total_cost_str)?;
+    pub fn render_html(&self) -> anyhow::Result<String> {
+        let html = InvoiceHtml {
+            me: &self.me,
+            images: self
+                .me
+                .branding
+                .html_images()
+                .context("embedding branding images")?,
+            invoice: &self.invoice,
+            project: &self.project,
+            client: &self.client,
+            totals: self.invoice.totals(),
+        };
+
+        html.render().context("rendering invoice HTML template")
+    }
+
+    pub fn save_html(&self, config: &Config) -> anyhow::Result<PathBuf> {
+        let html_dir = get_html_dir(config).context("getting HTML directory")?;
+        let path = html_dir.join(self.html_filename());
+
+        let html = self.render_html()?;
+        std::fs::write(&path, html).context("writing invoice HTML file")?;
+
+        Ok(path)
+    }
+
+    fn placeholders(&self) -> Placeholders {
+        Placeholders {
+            client: self.client.name.to_string(),
+            project: self.project.name.to_string(),
+            year: self.invoice.date.year(),
+            number: self.invoice.number,
+        }
+    }
+
+    fn write_beancount_to<W: Write>(
+        &self,
+        config: &BeancountConfig,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        let totals = self.invoice.totals();
+        let placeholders = self.placeholders();
 
         let date = self.invoice.date.to_beancount();
-        let account_name: String = self
-            .client
-            .name
-            .to_string()
-            .chars()
-            .filter(|c| c.is_alphanumeric())
-            .collect();
-        let narration = format!("Invoice #{} - {}", self.invoice.number, self.project.name);
-        let src_account = Account::builder()
-            .ty(AccountType::Income)
-            .parts([account_name.into()].to_vec())
-            .build();
-        let dst_account = Account::builder()
-            .ty(AccountType::Assets)
-            .parts(["AccountsReceivable".into()].to_vec())
-            .build();
-        let amount = Amount::builder()
-            .num(total_cost_decimal)
-            .currency("USD".into())
-            .build();
-        let src_posting = Posting::builder()
-            .account(src_account)
-            .units(amount.clone().into())
-            .build();
-        let dst_posting = Posting::builder()
-            .account(dst_account)
-            .units(amount.into())
-            .build();
-        let txn = Transaction::builder()
-            .date(date)
-            .postings([src_posting, dst_posting].to_vec())
-            .narration(narration.into())
-            .build();
+        let narration = placeholders.fill(&config.invoice_narration);
+        let income_account = placeholders
+            .fill_account(&config.income_account)
+            .context("getting income account")?;
+        let tax_account = placeholders
+            .fill_account(&config.tax_account)
+            .context("getting tax account")?;
+        let receivable_account = placeholders
+            .fill_account(&config.receivable_account)
+            .context("getting receivable account")?;
+        let amount = |money: Money| {
+            Amount::builder()
+                .num(money.as_decimal())
+                .currency(self.invoice.currency.to_string().into())
+                .build()
+        };
 
-        let renderer = BasicRenderer::new();
+        let mut postings = Vec::new();
+        postings.push(
+            Posting::builder()
+                .account(income_account)
+                .units(amount(-totals.before_tax()).into())
+                .build(),
+        );
+        let tax = totals.tax();
+        if !tax.is_zero() {
+            postings.push(
+                Posting::builder()
+                    .account(tax_account)
+                    .units(amount(-tax).into())
+                    .build(),
+            );
+        }
+        postings.push(
+            Posting::builder()
+                .account(receivable_account)
+                .units(amount(totals.total).into())
+                .build(),
+        );
 
-        renderer.render(&txn, writer)?;
+        let txn = ConfiguredTransaction::new(config, &placeholders, date, narration, postings);
+        txn.write_to(writer)?;
 
         Ok(())
     }
 
-    pub fn write_beancount_to_string(&self) -> anyhow::Result<String> {
+    pub fn write_beancount_to_string(&self, config: &BeancountConfig) -> anyhow::Result<String> {
         let mut buf = Vec::<u8>::new();
-        self.write_beancount_to(&mut buf)?;
+        self.write_beancount_to(config, &mut buf)?;
         let string = String::from_utf8(buf)?;
 
         Ok(string)
     }
 
-    pub fn save_beancount(&self, config: &Config) -> anyhow::Result<PathBuf> {
+    /// Save the invoice transaction and update the index,
+    /// unless beancount output is disabled.
+    pub fn save_beancount(&self, config: &Config) -> anyhow::Result<Option<PathBuf>> {
+        if !config.beancount.enabled {
+            return Ok(None);
+        }
+
+        let out_path = self.write_beancount(config)?;
+        rebuild_index(config).context("rebuilding beancount index")?;
+
+        Ok(Some(out_path))
+    }
+
+    /// Save the invoice transaction without updating the index.
+    pub fn write_beancount(&self, config: &Config) -> anyhow::Result<PathBuf> {
         let beancount_dir = get_beancount_dir(config).context("getting beancount directory")?;
         let filename = format!("Invoice_{}.beancount", self.invoice.number);
         let out_path = beancount_dir.join(&filename);
         let mut out_file = File::create(&out_path)?;
 
-        self.write_beancount_to(&mut out_file)?;
+        self.write_beancount_to(&config.beancount, &mut out_file)?;
 
         Ok(out_path)
     }
@@ -398,17 +1240,14 @@ mod test {
     use std::{borrow::Cow, str::FromStr};
 
     use crate::{
-        address::MailingAddress,
-        client::Client,
-        contact::ContactInfo,
-        date::DateString,
+        config::BeancountConfig,
+        discount::{Discount, DiscountAmount},
         id::Id,
-        me::{Me, PaymentMethod},
-        price::PriceUSD,
-        project::Project,
+        money::{Currency, Money, Percentage, Quantity},
+        test_util::create_full_test_invoice,
     };
 
-    use super::{FullInvoice, Invoice, LineItem};
+    use super::{Billed, DiscountLine, Invoice, InvoiceArgs, LineItem, TaxLine, Totals};
 
     use beancount_core::{Account, AccountType, Amount, Directive, Ledger, Posting, Transaction};
     use rust_decimal::Decimal;
@@ -421,7 +1260,11 @@ mod test {
             project_ref: Id::new("Manhattan".to_owned()),
             date: date!(2023 - 02 - 17).try_into()?,
             due_date: date!(2023 - 02 - 24).try_into()?,
+            currency: Currency::default(),
+            tax_rate: None,
             items: Vec::new(),
+            expenses: Vec::new(),
+            discounts: Vec::new(),
             conditions: None,
         };
 
@@ -429,7 +1272,10 @@ mod test {
 project_ref: Manhattan
 date: 2023-02-17
 due_date: 2023-02-24
+currency: USD
+tax_rate: null
 items: []
+discounts: []
 conditions: null
 "#;
 
@@ -453,7 +1299,11 @@ items: []
             project_ref: Id::new("Manhattan".to_owned()),
             date: date!(2023 - 02 - 17).try_into()?,
             due_date: date!(2023 - 02 - 24).try_into()?,
+            currency: Currency::default(),
+            tax_rate: None,
             items: Vec::new(),
+            expenses: Vec::new(),
+            discounts: Vec::new(),
             conditions: None,
         };
 
@@ -464,77 +1314,77 @@ items: []
         Ok(())
     }
 
-    fn create_full_test_invoice() -> FullInvoice {
-        FullInvoice {
-            me: Me {
-                name: "Test User".to_owned(),
-                address: MailingAddress {
-                    addr1: "123 Test Street".to_owned(),
-                    addr2: None,
-                    addr3: None,
-                    city: "Twin Falls".to_owned(),
-                    state: "Idaho".to_owned(),
-                    zip: "12345".to_owned(),
-                },
-                contact: ContactInfo {
-                    email: "test@example.com".to_owned(),
-                    phone: "(123) 456-7890".to_owned(),
-                },
-                payment: [PaymentMethod {
-                    name: "PayPal".to_owned(),
-                    display_text: None,
-                    url: None,
-                }]
-                .to_vec(),
-            },
-            invoice: Invoice {
-                number: 17,
-                project_ref: "Test Project #1".to_owned().into(),
-                date: DateString::try_new("2023-01-07".to_owned()).unwrap(),
-                due_date: DateString::try_new("2023-01-21".to_owned()).unwrap(),
-                items: [
-                    LineItem {
-                        description: "Test the first thing".to_owned(),
-                        quantity: 1.0,
-                        unit_price: PriceUSD::from_str("10.3").unwrap(),
-                    },
-                    LineItem {
-                        description: "Test the second thing".to_owned(),
-                        quantity: 2.0,
-                        unit_price: PriceUSD::from_str("9.6").unwrap(),
-                    },
-                ]
-                .to_vec(),
-                conditions: None,
-            },
-            project: Project {
-                name: "Test Project #1".to_owned().into(),
-                description: "A great project for testing".to_owned(),
-                client_ref: "Test Client #1".to_owned().into(),
-            },
-            client: Client {
-                name: "Test Client #1".to_owned().into(),
-                address: MailingAddress {
-                    addr1: "124 Test Avenue".to_owned(),
-                    addr2: None,
-                    addr3: None,
-                    city: "New York".to_owned(),
-                    state: "New York".to_owned(),
-                    zip: "54321".to_owned(),
-                },
-                contact: ContactInfo {
-                    email: "client@example.com".to_owned(),
-                    phone: "(321) 654-0987".to_owned(),
-                },
-            },
-        }
+    #[test]
+    fn test_parse_line_item() -> anyhow::Result<()> {
+        let expected = LineItem {
+            description: "Consulting: phase 1".to_owned(),
+            quantity: Quantity::from_str("2.5").unwrap(),
+            unit_price: Money::from_str("80").unwrap(),
+            tax_rate: None,
+            discount: None,
+        };
+
+        let actual: LineItem = "Consulting: phase 1:2.5:80".parse()?;
+
+        assert_eq!(actual, expected);
+        assert!("No quantity or price".parse::<LineItem>().is_err());
+        assert!("Bad quantity:lots:80".parse::<LineItem>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invoice_args_is_complete() -> anyhow::Result<()> {
+        let project_only = InvoiceArgs {
+            project: Some(Id::new("Manhattan".to_owned())),
+            ..Default::default()
+        };
+        assert!(!project_only.is_complete());
+
+        let with_item = InvoiceArgs {
+            items: vec!["Consulting:2.5:80".parse()?],
+            ..project_only.clone()
+        };
+        assert!(with_item.is_complete());
+
+        let billing_time = InvoiceArgs {
+            bill_time: true,
+            ..project_only
+        };
+        assert!(billing_time.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_billed_retain_invoiced() -> anyhow::Result<()> {
+        let mut invoice = create_full_test_invoice().invoice;
+        let project = invoice.project_ref.clone();
+        let kept: LineItem = "Flights (January 3, 2023):1:420".parse()?;
+        let removed: LineItem = "Hotel (January 4, 2023):1:180".parse()?;
+        invoice.expenses = vec![kept.clone()];
+
+        let mut billed = Billed {
+            time_entries: Vec::new(),
+            expenses: vec![(1, kept.clone()), (2, removed), (3, kept)],
+        };
+        billed.retain_invoiced(&project, &invoice);
+        assert_eq!(billed.expenses.len(), 1);
+        assert_eq!(billed.expenses[0].0, 1);
+
+        invoice.project_ref = Id::new("Manhattan".to_owned());
+        billed.retain_invoiced(&project, &invoice);
+        assert!(billed.expenses.is_empty());
+
+        Ok(())
     }
 
     #[test]
     fn test_write_beancount() -> anyhow::Result<()> {
         let full_invoice = create_full_test_invoice();
 
-        let beancount_string = full_invoice.write_beancount_to_string()?;
+        let beancount_string =
+            full_invoice.write_beancount_to_string(&BeancountConfig::default())?;
 
         let ledger = beancount_parser::parse(&beancount_string)?;
 
@@ -547,17 +1397,21 @@ items: []
             .ty(AccountType::Assets)
             .parts([Cow::Borrowed("AccountsReceivable")].to_vec())
             .build();
-        let amount = Amount::builder()
+        let src_amount = Amount::builder()
+            .num(Decimal::from_str_exact("-29.50").unwrap())
+            .currency("USD".into())
+            .build();
+        let dst_amount = Amount::builder()
             .num(Decimal::from_str_exact("29.50").unwrap())
             .currency("USD".into())
             .build();
         let src_posting = Posting::builder()
             .account(src_account)
-            .units(amount.clone().into())
+            .units(src_amount.into())
             .build();
         let dst_posting = Posting::builder()
             .account(dst_account)
-            .units(amount.into())
+            .units(dst_amount.into())
             .build();
         let txn = Transaction::builder()
             .date(date)
@@ -565,7 +1419,7 @@ items: []
             .narration("Invoice #17 - Test Project #1".into())
             .source(Some(
                 r#"2023-01-07 * "Invoice #17 - Test Project #1"
-	Income:TestClient1	29.50 USD
+	Income:TestClient1	-29.50 USD
 	Assets:AccountsReceivable	29.50 USD
 "#,
             ))
@@ -582,4 +1436,139 @@ items: []
 
         Ok(())
     }
+
+    #[test]
+    fn test_totals_with_tax() -> anyhow::Result<()> {
+        let mut invoice = create_full_test_invoice().invoice;
+        invoice.tax_rate = Some(Percentage::from_str("10")?);
+        invoice.items[1].tax_rate = Some(Percentage::from_str("5.5")?);
+
+        let expected = Totals {
+            subtotal: Money::from_str("29.50")?,
+            discounts: Vec::new(),
+            taxes: [
+                TaxLine {
+                    rate: Percentage::from_str("5.5")?,
+                    taxable: Money::from_str("19.20")?,
+                    tax: Money::from_str("1.06")?,
+                },
+                TaxLine {
+                    rate: Percentage::from_str("10")?,
+                    taxable: Money::from_str("10.30")?,
+                    tax: Money::from_str("1.03")?,
+                },
+            ]
+            .to_vec(),
+            total: Money::from_str("31.59")?,
+        };
+
+        assert_eq!(invoice.totals(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_totals_with_discounts() -> anyhow::Result<()> {
+        let mut invoice = create_full_test_invoice().invoice;
+        invoice.tax_rate = Some(Percentage::from_str("10")?);
+        invoice.items[1].discount = Some(DiscountAmount::from_str("50%")?);
+        invoice.discounts = [Discount {
+            description: "Loyalty".to_owned(),
+            amount: DiscountAmount::from_str("5")?,
+        }]
+        .to_vec();
+
+        let totals = invoice.totals();
+
+        assert_eq!(totals.subtotal, Money::from_str("29.50")?);
+        assert_eq!(
+            totals.discounts,
+            [
+                DiscountLine {
+                    description: "Test the second thing (50%)".to_owned(),
+                    amount: Money::from_str("9.60")?,
+                },
+                DiscountLine {
+                    description: "Loyalty".to_owned(),
+                    amount: Money::from_str("5.00")?,
+                },
+            ]
+            .to_vec()
+        );
+        assert_eq!(totals.before_tax(), Money::from_str("14.90")?);
+        assert_eq!(totals.tax(), Money::from_str("1.49")?);
+        assert_eq!(totals.total, Money::from_str("16.39")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_totals_with_discounts_and_mixed_tax_rates() -> anyhow::Result<()> {
+        let mut invoice = create_full_test_invoice().invoice;
+        invoice.tax_rate = None;
+        invoice.items[0].tax_rate = Some(Percentage::from_str("10")?);
+        invoice.items[1].tax_rate = Some(Percentage::from_str("20")?);
+        invoice.discounts = [Discount {
+            description: "Loyalty".to_owned(),
+            amount: DiscountAmount::from_str("10%")?,
+        }]
+        .to_vec();
+
+        let totals = invoice.totals();
+
+        assert_eq!(
+            totals.taxes,
+            [
+                TaxLine {
+                    rate: Percentage::from_str("10")?,
+                    taxable: Money::from_str("9.27")?,
+                    tax: Money::from_str("0.93")?,
+                },
+                TaxLine {
+                    rate: Percentage::from_str("20")?,
+                    taxable: Money::from_str("17.28")?,
+                    tax: Money::from_str("3.46")?,
+                },
+            ]
+            .to_vec()
+        );
+        assert_eq!(totals.before_tax(), Money::from_str("26.55")?);
+        assert_eq!(totals.total, Money::from_str("30.94")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_html() -> anyhow::Result<()> {
+        let mut full_invoice = create_full_test_invoice();
+        full_invoice.invoice.conditions = Some("Payable in <30> days".to_owned());
+
+        let html = full_invoice.render_html()?;
+
+        assert!(html.contains("<title>Invoice #17 - Test User</title>"));
+        assert!(html.contains("<td>Test the second thing</td>"));
+        assert!(html.contains("29.50 USD"));
+        assert!(html.contains("Payable in &lt;30&gt; days"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_beancount_with_tax() -> anyhow::Result<()> {
+        let mut full_invoice = create_full_test_invoice();
+        full_invoice.invoice.tax_rate = Some(Percentage::from_str("10")?);
+
+        let beancount_string =
+            full_invoice.write_beancount_to_string(&BeancountConfig::default())?;
+
+        let expected_postings = r#"
+	Income:TestClient1	-29.50 USD
+	Liabilities:SalesTax	-2.95 USD
+	Assets:AccountsReceivable	32.45 USD
+"#;
+
+        assert!(beancount_string.contains(expected_postings));
+
+        Ok(())
+    }
 }
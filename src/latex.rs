@@ -1,9 +1,19 @@
 use std::path::Path;
+use std::process::Command;
 use std::{fmt, io};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
 use texrender::TexRender;
 
+use crate::{
+    client::Client,
+    config::Config,
+    me::Me,
+    project::Project,
+    templates::{render_latex, typst_assets},
+};
+
 // Inspired by https://users.rust-lang.org/t/why-doesnt-vec-u8-implement-std-fmt-write/13200/5
 pub struct ToFmtWrite<T>(pub T);
 
@@ -50,6 +60,104 @@ pub struct Asset {
     pub filename: String,
 }
 
+impl Asset {
+    /// The document class used by the built-in templates.
+    pub fn invoice_class() -> Self {
+        Self {
+            data: include_bytes!("../assets/CSMinimalInvoice.cls").to_vec(),
+            filename: "CSMinimalInvoice.cls".to_owned(),
+        }
+    }
+
+    /// The built-in Typst templates and the helpers they share.
+    pub fn typst_templates() -> Vec<Self> {
+        [
+            (
+                "pdoc.typ",
+                include_bytes!("../assets/typst/pdoc.typ").as_slice(),
+            ),
+            (
+                "invoice.typ",
+                include_bytes!("../assets/typst/invoice.typ").as_slice(),
+            ),
+            (
+                "receipt.typ",
+                include_bytes!("../assets/typst/receipt.typ").as_slice(),
+            ),
+            (
+                "credit_note.typ",
+                include_bytes!("../assets/typst/credit_note.typ").as_slice(),
+            ),
+            (
+                "quote.typ",
+                include_bytes!("../assets/typst/quote.typ").as_slice(),
+            ),
+        ]
+        .into_iter()
+        .map(|(filename, data)| Self {
+            data: data.to_vec(),
+            filename: filename.to_owned(),
+        })
+        .collect()
+    }
+}
+
+/// How documents are typeset into PDFs, chosen with `renderer` in the `[pdf]` config section.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// LaTeX templates compiled with `latexmk`.
+    #[default]
+    Latex,
+    /// Typst templates compiled with the `typst` command.
+    Typst,
+}
+
+/// A document to typeset, with everything its templates are chosen by.
+pub struct Document<'a, T, C> {
+    /// The name of its templates without extension, e.g. `invoice`.
+    pub name: &'a str,
+    /// The built-in LaTeX template.
+    pub latex: &'a T,
+    /// The data available to Typst and user-supplied templates.
+    pub context: &'a C,
+    pub me: &'a Me,
+    pub project: &'a Project,
+    pub client: &'a Client,
+}
+
+impl<T: askama::Template, C: Serialize> Document<'_, T, C> {
+    /// Typeset the document with the configured renderer.
+    /// With `show_source`, print the LaTeX source or the Typst data first.
+    pub fn render_pdf(
+        &self,
+        pdf_output_path: &Path,
+        config: &Config,
+        show_source: bool,
+    ) -> anyhow::Result<()> {
+        if config.pdf.renderer == Renderer::Typst {
+            let assets = typst_assets(self, config).context("collecting Typst templates")?;
+            if show_source {
+                let data = serde_json::to_string_pretty(self.context)
+                    .context("serializing document data")?;
+                println!("Typst data:\n\n{}", data);
+            }
+
+            return compile_typst(&format!("{}.typ", self.name), pdf_output_path, &assets)
+                .context("compiling Typst to PDF");
+        }
+
+        let (rendered_tex, assets) =
+            render_latex(self, config).context("rendering LaTeX template")?;
+
+        if show_source {
+            println!("Final LaTeX:\n\n{}", &rendered_tex);
+        }
+
+        compile_latex(&rendered_tex, pdf_output_path, &assets).context("compiling LaTeX to PDF")
+    }
+}
+
 pub fn compile_latex(
     tex: &str,
     pdf_output_path: impl AsRef<Path>,
@@ -69,3 +177,34 @@ pub fn compile_latex(
 
     Ok(())
 }
+
+/// Compile the Typst file `main`, one of the assets, with the `typst` command.
+pub fn compile_typst(
+    main: &str,
+    pdf_output_path: impl AsRef<Path>,
+    assets: &[Asset],
+) -> anyhow::Result<()> {
+    let build_dir = tempfile::tempdir().context("creating Typst build directory")?;
+    for asset in assets {
+        std::fs::write(build_dir.path().join(&asset.filename), &asset.data)
+            .with_context(|| format!("writing Typst asset {:?}", asset.filename))?;
+    }
+
+    let output = Command::new("typst")
+        .args(["compile", main, "output.pdf"])
+        .current_dir(build_dir.path())
+        .output()
+        .context("running typst, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "typst failed to compile {}:\n{}",
+            main,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    std::fs::copy(build_dir.path().join("output.pdf"), pdf_output_path)
+        .context("writing rendered PDF to file")?;
+
+    Ok(())
+}
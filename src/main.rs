@@ -1,51 +1,457 @@
-use anyhow::Context;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
 use clap::{Parser, Subcommand};
-use cli::print_title;
+use cli::{print_header, print_title};
 use config::Config;
 use project::Project;
 
-use crate::{client::Client, invoice::Invoice, receipt::Receipt};
+use crate::{
+    beancount::rebuild_index,
+    browse::{
+        list_invoices, list_projects, list_receipts, show_receipt, InvoiceDetails, ListFilters,
+        PaymentStatus, ShowFormat,
+    },
+    client::Client,
+    credit_note::CreditNote,
+    date::{today, DateString},
+    expense::Expense,
+    id::Id,
+    invoice::{Billed, Invoice, InvoiceArgs},
+    money::{Currency, Money, Percentage},
+    quote::Quote,
+    receipt::{Receipt, ReceiptNumber},
+    recurring::RecurringInvoice,
+    report::{
+        aging_report, render_report, revenue_report, GroupBy, OutputFormat, OutstandingInvoice,
+        RevenueEntry,
+    },
+    storage::{find_client, find_project},
+    timelog::{Hours, TimeEntry, TimeLog},
+};
 
 mod address;
+mod beancount;
+mod browse;
 mod cli;
 mod client;
 mod completion;
 mod config;
 mod contact;
+mod credit_note;
 mod date;
+mod discount;
+mod expense;
 mod id;
 mod invoice;
 mod latex;
 mod me;
-mod price;
+mod money;
 mod project;
+mod quote;
 mod receipt;
+mod recurring;
+mod references;
+mod report;
 mod storage;
+mod templates;
+#[cfg(test)]
+mod test_util;
+mod text;
+mod timelog;
 
 #[derive(Subcommand)]
 enum Command {
-    /// Get or create client.
-    Client,
+    /// Get or create client, or edit, rename or delete one.
+    Client {
+        #[command(subcommand)]
+        command: Option<ClientCommand>,
+    },
     /// List all saved clients.
     ListClients,
     /// Generate an invoice.
+    ///
+    /// Prompts for all information unless `--from` is given, or `--project`
+    /// with line items from `--item`, `--bill-time` or `--bill-expenses`.
     Invoice {
         /// Print latex source before rendering
         #[arg(long)]
         show_tex: bool,
+        /// Read the invoice from a YAML file instead of prompting
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = [
+                "number",
+                "project",
+                "date",
+                "days_to_pay",
+                "currency",
+                "tax_rate",
+                "items",
+                "discounts",
+                "conditions",
+                "bill_time",
+                "hourly_rate",
+                "bill_expenses",
+            ]
+        )]
+        from: Option<PathBuf>,
+        #[command(flatten)]
+        args: InvoiceArgs,
+        #[arg(long, value_enum, default_value_t)]
+        format: DocumentFormat,
     },
     /// Generate a receipt.
     Receipt {
         /// Print latex source before rendering
         #[arg(long)]
         show_tex: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: DocumentFormat,
+    },
+    /// Generate a credit note reversing all or part of an invoice.
+    CreditNote {
+        /// Print latex source before rendering
+        #[arg(long)]
+        show_tex: bool,
+    },
+    /// Generate a quote, or convert one into an invoice.
+    Quote {
+        #[command(subcommand)]
+        command: Option<QuoteCommand>,
+        /// Print latex source before rendering
+        #[arg(long)]
+        show_tex: bool,
+    },
+    /// Get or create project, or edit, rename or delete one.
+    Project {
+        #[command(subcommand)]
+        command: Option<ProjectCommand>,
+    },
+    /// Re-render PDFs and beancount files from saved YAML.
+    Render {
+        #[command(subcommand)]
+        target: Option<RenderTarget>,
+        /// Re-render every saved invoice, receipt, credit note and quote
+        /// (only invoices and receipts for HTML)
+        #[arg(long)]
+        all: bool,
+        /// Print latex source before rendering
+        #[arg(long)]
+        show_tex: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: DocumentFormat,
+    },
+    /// Track money spent on projects.
+    Expense {
+        #[command(subcommand)]
+        command: ExpenseCommand,
+    },
+    /// Track time spent on projects.
+    Time {
+        #[command(subcommand)]
+        command: TimeCommand,
+    },
+    /// Manage recurring invoices.
+    Recurring {
+        #[command(subcommand)]
+        command: RecurringCommand,
+    },
+    /// List saved projects, invoices or receipts.
+    List {
+        #[command(subcommand)]
+        target: ListTarget,
+    },
+    /// Show a saved document with everything computed from it.
+    Show {
+        #[command(subcommand)]
+        target: ShowTarget,
+        #[arg(long, value_enum, default_value_t, global = true)]
+        format: ShowFormat,
+    },
+    /// Summarize invoices and payments.
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Manage the generated beancount files.
+    Beancount {
+        #[command(subcommand)]
+        command: BeancountCommand,
     },
-    /// Get or create project.
-    Project,
     // Edit personal info.
     // Me,
 }
 
+/// The kind of file generated for a document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+enum DocumentFormat {
+    /// PDF rendered with LaTeX
+    #[default]
+    Pdf,
+    /// A single HTML file with inline CSS, for invoices and receipts
+    Html,
+}
+
+impl Command {
+    /// Whether the command prints CSV, JSON or text for pasting, which the title would corrupt.
+    fn is_machine_readable(&self) -> bool {
+        match self {
+            Command::Report {
+                command: ReportCommand::Aging { format, .. } | ReportCommand::Revenue { format, .. },
+            }
+            | Command::List {
+                target:
+                    ListTarget::Projects { format, .. }
+                    | ListTarget::Invoices { format, .. }
+                    | ListTarget::Receipts { format, .. },
+            } => *format != OutputFormat::Table,
+            Command::Show { format, .. } => *format != ShowFormat::Human,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ClientCommand {
+    /// Edit a client's YAML.
+    Edit { name: Id },
+    /// Rename a client and update its projects.
+    Rename { old: Id, new: Id },
+    /// Delete a client which has no projects.
+    Delete {
+        name: Id,
+        /// Also delete the client's projects and all their documents
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectCommand {
+    /// Edit a project's YAML.
+    Edit { name: Id },
+    /// Rename a project and update every document referring to it.
+    Rename { old: Id, new: Id },
+    /// Delete a project which no document refers to.
+    Delete {
+        name: Id,
+        /// Also delete the invoices, receipts, credit notes, quotes, expenses,
+        /// recurring invoices and time log of the project
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TimeCommand {
+    /// Log time spent on a project.
+    Add {
+        /// Project name
+        #[arg(long)]
+        project: Id,
+        /// Duration in hours (e.g. 1.5) or as e.g. 1h30m
+        #[arg(long)]
+        duration: Hours,
+        /// What the time was spent on
+        #[arg(long)]
+        description: String,
+        /// Date as YYYY-MM-DD (defaults to today)
+        #[arg(long)]
+        date: Option<DateString>,
+        /// Don't bill this time on invoices
+        #[arg(long)]
+        non_billable: bool,
+        /// Hourly rate, overriding the rate given when invoicing
+        #[arg(long)]
+        rate: Option<Money>,
+    },
+    /// Start a timer for a project.
+    Start {
+        /// Project name
+        #[arg(long)]
+        project: Id,
+        /// What the time is spent on
+        #[arg(long)]
+        description: String,
+    },
+    /// Stop a project's timer and log the elapsed time.
+    Stop {
+        /// Project name
+        #[arg(long)]
+        project: Id,
+    },
+    /// List logged time.
+    List {
+        /// Only list time for this project
+        #[arg(long)]
+        project: Option<Id>,
+        /// Only list billable time which hasn't been invoiced yet
+        #[arg(long)]
+        unbilled: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExpenseCommand {
+    /// Record money spent on a project.
+    Add {
+        /// Project name
+        #[arg(long)]
+        project: Id,
+        /// Amount spent
+        #[arg(long)]
+        amount: Money,
+        /// Category, e.g. Travel or Hosting
+        #[arg(long)]
+        category: String,
+        /// What the money was spent on
+        #[arg(long)]
+        description: String,
+        /// Date as YYYY-MM-DD (defaults to today)
+        #[arg(long)]
+        date: Option<DateString>,
+        /// Currency code (defaults to the project's or client's currency)
+        #[arg(long)]
+        currency: Option<Currency>,
+        /// Path to a scan or PDF of the receipt
+        #[arg(long)]
+        receipt: Option<PathBuf>,
+        /// Bill this expense back to the client on the next invoice
+        #[arg(long)]
+        rebillable: bool,
+        /// Percentage added on top of the amount when billing it back
+        #[arg(long, requires = "rebillable")]
+        markup: Option<Percentage>,
+        /// Beancount account the expense was paid from (defaults to the configured one)
+        #[arg(long)]
+        paid_from: Option<String>,
+    },
+    /// List recorded expenses.
+    List {
+        /// Only list expenses for this project
+        #[arg(long)]
+        project: Option<Id>,
+        /// Only list rebillable expenses which haven't been invoiced yet
+        #[arg(long)]
+        unbilled: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RecurringCommand {
+    /// Generate and render every recurring invoice which is due.
+    Run {
+        /// Generate invoices due up to this date, as YYYY-MM-DD (defaults to today)
+        #[arg(long)]
+        as_of: Option<DateString>,
+        /// Print latex source before rendering
+        #[arg(long)]
+        show_tex: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ListTarget {
+    /// List projects.
+    Projects {
+        /// Only list projects for this client
+        #[arg(long)]
+        client: Option<Id>,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// List invoices.
+    Invoices {
+        #[command(flatten)]
+        filters: ListFilters,
+        /// Only list invoices with this payment status
+        #[arg(long, value_enum)]
+        status: Option<PaymentStatus>,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// List receipts.
+    Receipts {
+        #[command(flatten)]
+        filters: ListFilters,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum ShowTarget {
+    /// Show an invoice with its totals, credit notes and payments.
+    Invoice { number: u32 },
+    /// Show a receipt, e.g. 17 (or 17-2 for the second payment towards invoice 17).
+    Receipt { number: ReceiptNumber },
+}
+
+#[derive(Subcommand)]
+enum ReportCommand {
+    /// Show outstanding balances per client by days past the due date.
+    Aging {
+        /// Count days past due up to this date, as YYYY-MM-DD (defaults to today)
+        #[arg(long)]
+        as_of: Option<DateString>,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Show amounts invoiced (accrual basis) and received (cash basis) per group.
+    Revenue {
+        /// Only count invoices, credit notes and receipts dated on or after this date
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<DateString>,
+        /// Only count invoices, credit notes and receipts dated on or before this date
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<DateString>,
+        #[arg(long, value_enum)]
+        group_by: GroupBy,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum BeancountCommand {
+    /// Regenerate the beancount files of all saved invoices, receipts, credit notes and expenses,
+    /// and the pdoc.beancount index which includes them.
+    Rebuild,
+}
+
+#[derive(Subcommand)]
+enum QuoteCommand {
+    /// Create an invoice from a quote's project, items and terms.
+    Convert {
+        /// Quote number
+        number: u32,
+        /// Invoice number (defaults to the next available number)
+        #[arg(long)]
+        invoice_number: Option<u32>,
+        /// Invoice date as YYYY-MM-DD (defaults to today)
+        #[arg(long)]
+        date: Option<DateString>,
+        /// Number of days after the invoice date that payment is due
+        /// (defaults to the project's or client's, or else 7)
+        #[arg(long)]
+        days_to_pay: Option<u16>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RenderTarget {
+    /// Re-render an invoice.
+    Invoice { number: u32 },
+    /// Re-render a receipt, e.g. 17 (or 17-2 for the second payment towards invoice 17).
+    Receipt { number: ReceiptNumber },
+    /// Re-render a credit note.
+    CreditNote { number: u32 },
+    /// Re-render a quote.
+    Quote { number: u32 },
+}
+
 #[derive(Parser)]
 struct Opts {
     #[command(subcommand)]
@@ -61,43 +467,699 @@ fn get_or_create_client(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn generate_invoice(config: &Config, show_tex: bool) -> anyhow::Result<()> {
-    let invoice =
-        Invoice::create_from_user_input(config).context("creating invoice from user input")?;
+fn generate_invoice(
+    config: &Config,
+    show_tex: bool,
+    from: Option<PathBuf>,
+    args: InvoiceArgs,
+    format: DocumentFormat,
+) -> anyhow::Result<()> {
+    let (invoice, billed) = if let Some(path) = from {
+        let invoice = Invoice::load_from_path(&path)
+            .with_context(|| format!("loading invoice from {:?}", path))?;
+        invoice.validate(config).context("validating invoice")?;
+        (invoice, Billed::default())
+    } else if args.is_complete() {
+        let (invoice, billed) =
+            Invoice::create_from_args(args, config).context("creating invoice from arguments")?;
+        invoice.validate(config).context("validating invoice")?;
+        (invoice, billed)
+    } else if args.project.is_some() {
+        bail!("--project needs line items from --item, --bill-time or --bill-expenses");
+    } else {
+        Invoice::create_from_user_input(config).context("creating invoice from user input")?
+    };
     invoice.save(config).context("saving invoice yaml")?;
 
+    billed
+        .mark(&invoice, config)
+        .context("marking time and expenses as billed")?;
+
+    render_invoice(invoice, config, show_tex, format)
+}
+
+/// Render an invoice to PDF or HTML, and beancount.
+fn render_invoice(
+    invoice: Invoice,
+    config: &Config,
+    show_tex: bool,
+    format: DocumentFormat,
+) -> anyhow::Result<()> {
     let full_invoice = invoice
         .collect(config)
         .context("collecting all invoice information")?;
 
-    println!("\nGenerating PDF...");
-    let pdf_path = full_invoice
-        .save_pdf(config, show_tex)
-        .context("saving invoice PDF")?;
-    println!("Invoice PDF saved to {:?}", pdf_path);
+    match format {
+        DocumentFormat::Pdf => {
+            println!("\nGenerating PDF...");
+            let pdf_path = full_invoice
+                .save_pdf(config, show_tex)
+                .context("saving invoice PDF")?;
+            println!("Invoice PDF saved to {:?}", pdf_path);
+        }
+        DocumentFormat::Html => {
+            let html_path = full_invoice
+                .save_html(config)
+                .context("saving invoice HTML")?;
+            println!("Invoice HTML saved to {:?}", html_path);
+        }
+    }
 
     let beancount_path = full_invoice
         .save_beancount(config)
         .context("saving invoice beancount file")?;
-    println!("Invoice beancount file saved to {:?}", beancount_path);
+    if let Some(beancount_path) = beancount_path {
+        println!("Invoice beancount file saved to {:?}", beancount_path);
+    }
 
     Ok(())
 }
 
-fn generate_receipt(config: &Config, show_tex: bool) -> anyhow::Result<()> {
+fn generate_receipt(config: &Config, show_tex: bool, format: DocumentFormat) -> anyhow::Result<()> {
     let receipt =
         Receipt::create_from_user_input(config).context("creating receipt from user input")?;
     receipt.save(config).context("saving receipt")?;
 
+    render_receipt(receipt, config, show_tex, format)
+}
+
+/// Render a receipt to PDF or HTML, and beancount.
+fn render_receipt(
+    receipt: Receipt,
+    config: &Config,
+    show_tex: bool,
+    format: DocumentFormat,
+) -> anyhow::Result<()> {
     let full_receipt = receipt
         .collect(config)
         .context("collecting all receipt information")?;
 
+    match format {
+        DocumentFormat::Pdf => {
+            println!("\nGenerating PDF...");
+            let path = full_receipt
+                .save_pdf(config, show_tex)
+                .context("saving receipt PDF")?;
+            println!("Receipt PDF saved to {:?}", path);
+        }
+        DocumentFormat::Html => {
+            let path = full_receipt
+                .save_html(config)
+                .context("saving receipt HTML")?;
+            println!("Receipt HTML saved to {:?}", path);
+        }
+    }
+
+    let beancount_path = full_receipt
+        .save_beancount(config)
+        .context("saving receipt beancount file")?;
+    if let Some(beancount_path) = beancount_path {
+        println!("Receipt beancount file saved to {:?}", beancount_path);
+    }
+
+    Ok(())
+}
+
+fn generate_credit_note(config: &Config, show_tex: bool) -> anyhow::Result<()> {
+    let credit_note = CreditNote::create_from_user_input(config)
+        .context("creating credit note from user input")?;
+    credit_note.save(config).context("saving credit note")?;
+
+    render_credit_note(credit_note, config, show_tex)
+}
+
+/// Render a credit note to PDF and beancount.
+fn render_credit_note(
+    credit_note: CreditNote,
+    config: &Config,
+    show_tex: bool,
+) -> anyhow::Result<()> {
+    let full_credit_note = credit_note
+        .collect(config)
+        .context("collecting all credit note information")?;
+
+    println!("\nGenerating PDF...");
+    let path = full_credit_note
+        .save_pdf(config, show_tex)
+        .context("saving credit note PDF")?;
+    println!("Credit note PDF saved to {:?}", path);
+
+    let beancount_path = full_credit_note
+        .save_beancount(config)
+        .context("saving credit note beancount file")?;
+    if let Some(beancount_path) = beancount_path {
+        println!("Credit note beancount file saved to {:?}", beancount_path);
+    }
+
+    Ok(())
+}
+
+fn generate_quote(config: &Config, show_tex: bool) -> anyhow::Result<()> {
+    let quote = Quote::create_from_user_input(config).context("creating quote from user input")?;
+    quote.save(config).context("saving quote yaml")?;
+
+    render_quote(quote, config, show_tex)
+}
+
+/// Render a quote to PDF.
+fn render_quote(quote: Quote, config: &Config, show_tex: bool) -> anyhow::Result<()> {
+    let full_quote = quote
+        .collect(config)
+        .context("collecting all quote information")?;
+
     println!("\nGenerating PDF...");
-    let path = full_receipt
+    let pdf_path = full_quote
         .save_pdf(config, show_tex)
-        .context("saving receipt PDF")?;
-    println!("Receipt PDF saved to {:?}", path);
+        .context("saving quote PDF")?;
+    println!("Quote PDF saved to {:?}", pdf_path);
+
+    Ok(())
+}
+
+/// Create, save and render an invoice from a saved quote,
+/// and remember the invoice number in the quote.
+fn convert_quote(
+    config: &Config,
+    number: u32,
+    invoice_number: Option<u32>,
+    date: Option<DateString>,
+    days_to_pay: Option<u16>,
+    show_tex: bool,
+) -> anyhow::Result<()> {
+    let mut quote = Quote::load(number, config).context("loading quote")?;
+
+    let days_to_pay = match days_to_pay {
+        Some(days_to_pay) => days_to_pay,
+        None => find_project(&quote.project_ref, config)
+            .context("finding project")?
+            .default_days_to_pay(config)
+            .context("getting default days to pay")?,
+    };
+
+    let invoice_number = match invoice_number {
+        Some(invoice_number) => invoice_number,
+        None => Invoice::get_next_number(config).context("getting next invoice number")?,
+    };
+    let date = match date {
+        Some(date) => date.try_into().context("parsing invoice date")?,
+        None => today(),
+    };
+
+    if quote.is_expired(date)? {
+        eprintln!(
+            "Warning: quote {} expired on {}",
+            quote.number, quote.valid_until
+        );
+    }
+
+    let invoice = quote
+        .to_invoice(invoice_number, date, days_to_pay)
+        .context("converting quote to invoice")?;
+    invoice.validate(config).context("validating invoice")?;
+    invoice.save(config).context("saving invoice yaml")?;
+
+    quote.invoice_num = Some(invoice.number);
+    quote.save(config).context("saving converted quote yaml")?;
+    println!(
+        "Quote {} converted into invoice {}",
+        quote.number, invoice.number
+    );
+
+    render_invoice(invoice, config, show_tex, DocumentFormat::Pdf)
+}
+
+fn render_saved(
+    config: &Config,
+    target: Option<RenderTarget>,
+    all: bool,
+    show_tex: bool,
+    format: DocumentFormat,
+) -> anyhow::Result<()> {
+    match (target, all) {
+        (Some(RenderTarget::Invoice { number }), false) => {
+            let invoice = Invoice::load(number, config).context("loading invoice")?;
+            render_invoice(invoice, config, show_tex, format)
+                .with_context(|| format!("rendering invoice {}", number))
+        }
+        (Some(RenderTarget::Receipt { number }), false) => {
+            let receipt = Receipt::load(number, config).context("loading receipt")?;
+            render_receipt(receipt, config, show_tex, format)
+                .with_context(|| format!("rendering receipt {}", number))
+        }
+        (Some(RenderTarget::Quote { .. } | RenderTarget::CreditNote { .. }), false)
+            if format == DocumentFormat::Html =>
+        {
+            bail!("HTML output is only available for invoices and receipts")
+        }
+        (Some(RenderTarget::Quote { number }), false) => {
+            let quote = Quote::load(number, config).context("loading quote")?;
+            render_quote(quote, config, show_tex)
+                .with_context(|| format!("rendering quote {}", number))
+        }
+        (Some(RenderTarget::CreditNote { number }), false) => {
+            let credit_note = CreditNote::load(number, config).context("loading credit note")?;
+            render_credit_note(credit_note, config, show_tex)
+                .with_context(|| format!("rendering credit note {}", number))
+        }
+        (None, true) => render_all(config, show_tex, format),
+        _ => bail!("specify either a single document or --all"),
+    }
+}
+
+/// Re-render every saved document, reporting failures at the end
+/// rather than stopping at the first one.
+/// Only invoices and receipts are rendered to HTML.
+fn render_all(config: &Config, show_tex: bool, format: DocumentFormat) -> anyhow::Result<()> {
+    let mut failures = Vec::new();
+
+    let mut invoice_numbers = Invoice::list(config).context("listing invoices")?;
+    invoice_numbers.sort();
+    for number in invoice_numbers {
+        print_header(&format!("Invoice {}", number));
+        let result = Invoice::load(number, config)
+            .and_then(|invoice| render_invoice(invoice, config, show_tex, format));
+        if let Err(err) = result {
+            eprintln!("Error rendering invoice {}: {:?}", number, err);
+            failures.push(format!("invoice {}", number));
+        }
+    }
+
+    let mut receipt_numbers = Receipt::list(config).context("listing receipts")?;
+    receipt_numbers.sort();
+    for number in receipt_numbers {
+        print_header(&format!("Receipt {}", number));
+        let result = Receipt::load(number, config)
+            .and_then(|receipt| render_receipt(receipt, config, show_tex, format));
+        if let Err(err) = result {
+            eprintln!("Error rendering receipt {}: {:?}", number, err);
+            failures.push(format!("receipt {}", number));
+        }
+    }
+
+    if format == DocumentFormat::Pdf {
+        let mut credit_note_numbers = CreditNote::list(config).context("listing credit notes")?;
+        credit_note_numbers.sort();
+        for number in credit_note_numbers {
+            print_header(&format!("Credit note {}", number));
+            let result = CreditNote::load(number, config)
+                .and_then(|credit_note| render_credit_note(credit_note, config, show_tex));
+            if let Err(err) = result {
+                eprintln!("Error rendering credit note {}: {:?}", number, err);
+                failures.push(format!("credit note {}", number));
+            }
+        }
+
+        let mut quote_numbers = Quote::list(config).context("listing quotes")?;
+        quote_numbers.sort();
+        for number in quote_numbers {
+            print_header(&format!("Quote {}", number));
+            let result =
+                Quote::load(number, config).and_then(|quote| render_quote(quote, config, show_tex));
+            if let Err(err) = result {
+                eprintln!("Error rendering quote {}: {:?}", number, err);
+                failures.push(format!("quote {}", number));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!("failed to render {}", failures.join(", "));
+    }
+
+    Ok(())
+}
+
+fn run_time_command(config: &Config, command: TimeCommand) -> anyhow::Result<()> {
+    match command {
+        TimeCommand::Add {
+            project,
+            duration,
+            description,
+            date,
+            non_billable,
+            rate,
+        } => {
+            find_project(&project, config)
+                .with_context(|| format!("finding project {:?}", project))?;
+            let mut time_log = TimeLog::load(&project, config).context("loading time log")?;
+            time_log.entries.push(TimeEntry {
+                date: date.unwrap_or_else(|| today().into()),
+                hours: duration.0,
+                description,
+                billable: !non_billable,
+                rate,
+                invoice_num: None,
+            });
+            time_log.save(&project, config).context("saving time log")?;
+            println!("Logged {} hours for {}", duration.0, project);
+        }
+        TimeCommand::Start {
+            project,
+            description,
+        } => {
+            find_project(&project, config)
+                .with_context(|| format!("finding project {:?}", project))?;
+            let mut time_log = TimeLog::load(&project, config).context("loading time log")?;
+            time_log.start(description).context("starting timer")?;
+            time_log.save(&project, config).context("saving time log")?;
+            println!("Timer started for {}", project);
+        }
+        TimeCommand::Stop { project } => {
+            let mut time_log = TimeLog::load(&project, config).context("loading time log")?;
+            let entry = time_log.stop().context("stopping timer")?;
+            println!("Logged {} hours for {}", entry.hours, project);
+            time_log.save(&project, config).context("saving time log")?;
+        }
+        TimeCommand::List { project, unbilled } => {
+            let mut projects = match project {
+                Some(project) => vec![project],
+                None => TimeLog::list(config).context("listing time logs")?,
+            };
+            projects.sort_by_key(|project| project.to_string());
+
+            for project in projects {
+                let time_log = TimeLog::load(&project, config).context("loading time log")?;
+                let entries: Vec<&TimeEntry> = time_log
+                    .entries
+                    .iter()
+                    .filter(|entry| !unbilled || entry.is_unbilled())
+                    .collect();
+                if entries.is_empty() && time_log.running.is_none() {
+                    continue;
+                }
+
+                print_header(&project.to_string());
+                for entry in entries {
+                    let status = match (entry.billable, entry.invoice_num) {
+                        (false, _) => "non-billable".to_owned(),
+                        (true, Some(invoice_num)) => format!("billed on #{}", invoice_num),
+                        (true, None) => "unbilled".to_owned(),
+                    };
+                    println!(
+                        "- {}: {} h, {} ({})",
+                        entry.date, entry.hours, entry.description, status
+                    );
+                }
+                if let Some(running) = &time_log.running {
+                    println!(
+                        "- timer running since {}: {}",
+                        running.started_at, running.description
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_expense_command(config: &Config, command: ExpenseCommand) -> anyhow::Result<()> {
+    match command {
+        ExpenseCommand::Add {
+            project,
+            amount,
+            category,
+            description,
+            date,
+            currency,
+            receipt,
+            rebillable,
+            markup,
+            paid_from,
+        } => {
+            let project = find_project(&project, config)
+                .with_context(|| format!("finding project {:?}", project))?;
+            let currency = match currency {
+                Some(currency) => currency,
+                None => project
+                    .default_currency(config)
+                    .context("getting default currency")?,
+            };
+
+            let expense = Expense {
+                number: Expense::get_next_number(config).context("getting next expense number")?,
+                project_ref: project.name,
+                date: date.unwrap_or_else(|| today().into()),
+                category,
+                description,
+                amount,
+                currency,
+                receipt,
+                rebillable,
+                markup,
+                paid_from,
+                invoice_num: None,
+            };
+            expense.save(config).context("saving expense")?;
+            println!("Recorded expense {}", expense.number);
+
+            let beancount_path = expense
+                .collect(config)
+                .context("collecting all expense information")?
+                .save_beancount(config)
+                .context("saving expense beancount file")?;
+            if let Some(beancount_path) = beancount_path {
+                println!("Expense beancount file saved to {:?}", beancount_path);
+            }
+        }
+        ExpenseCommand::List { project, unbilled } => {
+            let mut numbers = Expense::list(config).context("listing expenses")?;
+            numbers.sort();
+
+            for number in numbers {
+                let expense = Expense::load(number, config)
+                    .with_context(|| format!("loading expense {}", number))?;
+                if project
+                    .as_ref()
+                    .is_some_and(|project| *project != expense.project_ref)
+                    || (unbilled && !expense.is_unbilled())
+                {
+                    continue;
+                }
+
+                let status = match (expense.rebillable, expense.invoice_num) {
+                    (false, _) => "not rebillable".to_owned(),
+                    (true, Some(invoice_num)) => format!("billed on #{}", invoice_num),
+                    (true, None) => "unbilled".to_owned(),
+                };
+                println!(
+                    "- #{} {}: {} {} for {}, {}: {} ({})",
+                    expense.number,
+                    expense.date,
+                    expense.amount,
+                    expense.currency,
+                    expense.project_ref,
+                    expense.category,
+                    expense.description,
+                    status
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Generate all due recurring invoices, recording them in their schedules
+/// so that running again doesn't generate them twice.
+fn run_recurring(config: &Config, as_of: Option<DateString>, show_tex: bool) -> anyhow::Result<()> {
+    let as_of = match as_of {
+        Some(date) => date.try_into().context("parsing --as-of date")?,
+        None => today(),
+    };
+
+    let mut failures = Vec::new();
+    // Invoices which were generated (and recorded) but couldn't be rendered,
+    // so that they can be rendered by hand rather than generated again
+    let mut unrendered = Vec::new();
+
+    let mut names = RecurringInvoice::list(config).context("listing recurring invoices")?;
+    names.sort();
+    for name in names {
+        print_header(&format!("Recurring invoice {}", name));
+        let result = RecurringInvoice::load(&name, config).and_then(|mut recurring| {
+            let invoices = recurring.generate_due(&name, as_of, config)?;
+            if invoices.is_empty() {
+                println!("No invoices due");
+            }
+            for invoice in invoices {
+                let number = invoice.number;
+                println!("Generated invoice {}", number);
+                if let Err(err) = render_invoice(invoice, config, show_tex, DocumentFormat::Pdf) {
+                    eprintln!("Error rendering invoice {}: {:?}", number, err);
+                    unrendered.push(number.to_string());
+                }
+            }
+
+            Ok(())
+        });
+        if let Err(err) = result {
+            eprintln!("Error running recurring invoice {}: {:?}", name, err);
+            failures.push(name);
+        }
+    }
+
+    let mut problems = Vec::new();
+    if !failures.is_empty() {
+        problems.push(format!(
+            "failed to run recurring invoices {}",
+            failures.join(", ")
+        ));
+    }
+    if !unrendered.is_empty() {
+        problems.push(format!(
+            "failed to render generated invoices {} (render them with `pdoc render invoice`)",
+            unrendered.join(", ")
+        ));
+    }
+    if !problems.is_empty() {
+        bail!("{}", problems.join("; "));
+    }
+
+    Ok(())
+}
+
+fn list_saved(config: &Config, target: ListTarget) -> anyhow::Result<()> {
+    let rendered = match target {
+        ListTarget::Projects { client, format } => {
+            let rows = list_projects(client.as_ref(), config).context("listing projects")?;
+            render_report(&rows, format)?
+        }
+        ListTarget::Invoices {
+            filters,
+            status,
+            format,
+        } => {
+            let rows =
+                list_invoices(&filters, status, today(), config).context("listing invoices")?;
+            render_report(&rows, format)?
+        }
+        ListTarget::Receipts { filters, format } => {
+            let rows = list_receipts(&filters, config).context("listing receipts")?;
+            render_report(&rows, format)?
+        }
+    };
+    print!("{}", rendered);
+
+    Ok(())
+}
+
+fn show_saved(config: &Config, target: ShowTarget, format: ShowFormat) -> anyhow::Result<()> {
+    match target {
+        ShowTarget::Invoice { number } => InvoiceDetails::load(number, today(), config)
+            .with_context(|| format!("loading invoice {}", number))?
+            .print(format),
+        ShowTarget::Receipt { number } => show_receipt(number, format, config)
+            .with_context(|| format!("showing receipt {}", number)),
+    }
+}
+
+fn report_aging(
+    config: &Config,
+    as_of: Option<DateString>,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let as_of = match as_of {
+        Some(date) => date.try_into().context("parsing --as-of date")?,
+        None => today(),
+    };
+
+    let outstanding =
+        OutstandingInvoice::load_all(as_of, config).context("loading outstanding invoices")?;
+    let rows = aging_report(&outstanding, as_of).context("computing aging report")?;
+    print!("{}", render_report(&rows, format)?);
+
+    Ok(())
+}
+
+fn report_revenue(
+    config: &Config,
+    from: Option<DateString>,
+    to: Option<DateString>,
+    group_by: GroupBy,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let from = from
+        .map(|date| date.try_into())
+        .transpose()
+        .context("parsing --from date")?;
+    let to = to
+        .map(|date| date.try_into())
+        .transpose()
+        .context("parsing --to date")?;
+
+    let entries = RevenueEntry::load_all(config).context("loading revenue")?;
+    let rows = revenue_report(&entries, from, to, group_by);
+    print!("{}", render_report(&rows, format)?);
+
+    Ok(())
+}
+
+/// Regenerate every beancount file from saved YAML, then the index.
+fn rebuild_beancount(config: &Config) -> anyhow::Result<()> {
+    if !config.beancount.enabled {
+        bail!("beancount output is disabled in the config");
+    }
+
+    let mut failures = Vec::new();
+
+    let mut invoice_numbers = Invoice::list(config).context("listing invoices")?;
+    invoice_numbers.sort();
+    for number in invoice_numbers {
+        let result = Invoice::load(number, config)
+            .and_then(|invoice| invoice.collect(config))
+            .and_then(|full_invoice| full_invoice.write_beancount(config));
+        if let Err(err) = result {
+            eprintln!("Error writing beancount for invoice {}: {:?}", number, err);
+            failures.push(format!("invoice {}", number));
+        }
+    }
+
+    let mut receipt_numbers = Receipt::list(config).context("listing receipts")?;
+    receipt_numbers.sort();
+    for number in receipt_numbers {
+        let result = Receipt::load(number, config)
+            .and_then(|receipt| receipt.collect(config))
+            .and_then(|full_receipt| full_receipt.write_beancount(config));
+        if let Err(err) = result {
+            eprintln!("Error writing beancount for receipt {}: {:?}", number, err);
+            failures.push(format!("receipt {}", number));
+        }
+    }
+
+    let mut credit_note_numbers = CreditNote::list(config).context("listing credit notes")?;
+    credit_note_numbers.sort();
+    for number in credit_note_numbers {
+        let result = CreditNote::load(number, config)
+            .and_then(|credit_note| credit_note.collect(config))
+            .and_then(|full_credit_note| full_credit_note.write_beancount(config));
+        if let Err(err) = result {
+            eprintln!(
+                "Error writing beancount for credit note {}: {:?}",
+                number, err
+            );
+            failures.push(format!("credit note {}", number));
+        }
+    }
+
+    let mut expense_numbers = Expense::list(config).context("listing expenses")?;
+    expense_numbers.sort();
+    for number in expense_numbers {
+        let result = Expense::load(number, config)
+            .and_then(|expense| expense.collect(config))
+            .and_then(|full_expense| full_expense.write_beancount(config));
+        if let Err(err) = result {
+            eprintln!("Error writing beancount for expense {}: {:?}", number, err);
+            failures.push(format!("expense {}", number));
+        }
+    }
+
+    let index_path = rebuild_index(config).context("rebuilding beancount index")?;
+    println!("Beancount index saved to {:?}", index_path);
+
+    if !failures.is_empty() {
+        bail!("failed to write beancount for {}", failures.join(", "));
+    }
 
     Ok(())
 }
@@ -144,36 +1206,149 @@ fn get_or_create_project(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
-// TODO re-render PDFs from yaml?
+fn run_client_command(config: &Config, command: ClientCommand) -> anyhow::Result<()> {
+    match command {
+        ClientCommand::Edit { name } => {
+            let client = Client::load(name.clone(), config).context("loading client")?;
+            let edited = client.edit_yaml().context("editing client yaml")?;
+            if edited.name != name {
+                bail!("use `pdoc client rename` to change the client's name");
+            }
+            edited.save(config).context("saving client yaml")?;
+
+            println!("Client {} saved", name);
+        }
+        ClientCommand::Rename { old, new } => {
+            Client::rename(&old, new.clone(), config).context("renaming client")?;
+
+            println!("Client {} renamed to {}", old, new);
+            rebuild_beancount_if_enabled(config)?;
+        }
+        ClientCommand::Delete { name, force } => {
+            Client::delete(&name, force, config).context("deleting client")?;
+
+            println!("Client {} deleted", name);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_project_command(config: &Config, command: ProjectCommand) -> anyhow::Result<()> {
+    match command {
+        ProjectCommand::Edit { name } => {
+            let project = find_project(&name, config).context("finding project")?;
+            let edited = project.edit_yaml().context("editing project yaml")?;
+            if edited.name != name {
+                bail!("use `pdoc project rename` to change the project's name");
+            }
+            find_client(&edited.client_ref, config)
+                .with_context(|| format!("finding client {}", edited.client_ref))?;
+            edited.save(config).context("saving project yaml")?;
+
+            println!("Project {} saved", name);
+        }
+        ProjectCommand::Rename { old, new } => {
+            Project::rename(&old, new.clone(), config).context("renaming project")?;
+
+            println!("Project {} renamed to {}", old, new);
+            rebuild_beancount_if_enabled(config)?;
+        }
+        ProjectCommand::Delete { name, force } => {
+            Project::delete(&name, force, config).context("deleting project")?;
+
+            println!("Project {} deleted", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrite the beancount files, whose narrations and accounts may
+/// contain client and project names.
+fn rebuild_beancount_if_enabled(config: &Config) -> anyhow::Result<()> {
+    if config.beancount.enabled {
+        rebuild_beancount(config).context("rebuilding beancount files")?;
+    }
+
+    Ok(())
+}
+
 // TODO finalize CLI
-// TODO beancount config in config.toml
-//      - enable/disable
-//      - AccountsReceivable name
-//      - income account name format
-//      - narration format?
-//      - payee? tags?
 // TODO edit me.yaml at startup if invalid
 // TODO switch from `time` crate to `chrono`?
-// TODO support non-USD currencies
 // TODO more `inquire` help texts (especially indicate which prompts are skippable)
-// TODO beancount decimal math
-// TODO beancount for receipts
-// TODO master beancount file that imports all others?
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
 
-    print_title("pdoc");
+    if !opts.command.is_machine_readable() {
+        print_title("pdoc");
+    }
 
     // Me::create_if_necessary()?;
 
     let config = Config::load()?;
 
     match opts.command {
-        Command::Client => get_or_create_client(&config)?,
+        Command::Client { command: None } => get_or_create_client(&config)?,
+        Command::Client {
+            command: Some(command),
+        } => run_client_command(&config, command)?,
         Command::ListClients => list_clients(&config)?,
-        Command::Invoice { show_tex } => generate_invoice(&config, show_tex)?,
-        Command::Receipt { show_tex } => generate_receipt(&config, show_tex)?,
-        Command::Project => get_or_create_project(&config)?,
+        Command::Invoice {
+            show_tex,
+            from,
+            args,
+            format,
+        } => generate_invoice(&config, show_tex, from, args, format)?,
+        Command::Receipt { show_tex, format } => generate_receipt(&config, show_tex, format)?,
+        Command::CreditNote { show_tex } => generate_credit_note(&config, show_tex)?,
+        Command::Quote {
+            command: None,
+            show_tex,
+        } => generate_quote(&config, show_tex)?,
+        Command::Quote {
+            command:
+                Some(QuoteCommand::Convert {
+                    number,
+                    invoice_number,
+                    date,
+                    days_to_pay,
+                }),
+            show_tex,
+        } => convert_quote(&config, number, invoice_number, date, days_to_pay, show_tex)?,
+        Command::Project { command: None } => get_or_create_project(&config)?,
+        Command::Project {
+            command: Some(command),
+        } => run_project_command(&config, command)?,
+        Command::Render {
+            target,
+            all,
+            show_tex,
+            format,
+        } => render_saved(&config, target, all, show_tex, format)?,
+        Command::Expense { command } => run_expense_command(&config, command)?,
+        Command::Time { command } => run_time_command(&config, command)?,
+        Command::Recurring {
+            command: RecurringCommand::Run { as_of, show_tex },
+        } => run_recurring(&config, as_of, show_tex)?,
+        Command::List { target } => list_saved(&config, target)?,
+        Command::Show { target, format } => show_saved(&config, target, format)?,
+        Command::Report {
+            command: ReportCommand::Aging { as_of, format },
+        } => report_aging(&config, as_of, format)?,
+        Command::Report {
+            command:
+                ReportCommand::Revenue {
+                    from,
+                    to,
+                    group_by,
+                    format,
+                },
+        } => report_revenue(&config, from, to, group_by, format)?,
+        Command::Beancount {
+            command: BeancountCommand::Rebuild,
+        } => rebuild_beancount(&config)?,
         // Command::Me => edit_personal_info(&config)?,
     }
 
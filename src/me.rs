@@ -1,21 +1,34 @@
-use std::{fmt::Display, fs::File, path::Path};
+use std::{
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::{
     address::MailingAddress,
+    beancount::account_component,
     cli::{print_header, YamlValidator},
     config::Config,
     contact::ContactInfo,
-    storage::get_data_dir,
+    latex::Asset,
+    storage::{expand_tilde, get_data_dir},
 };
 
+/// Image formats which `pdflatex` can include.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "pdf"];
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PaymentMethod {
     pub name: String,
     pub display_text: Option<String>,
     pub url: Option<String>,
+    /// Beancount account that payments made with this method are deposited to.
+    pub account: Option<String>,
 }
 
 impl PaymentMethod {
@@ -39,10 +52,18 @@ impl PaymentMethod {
                 // Convert Some("") to None
                 .filter(|line| !line.is_empty());
 
+            let account = inquire::Text::new("Beancount account?")
+                .with_placeholder(&Self::default_account(&name))
+                .prompt_skippable()
+                .context("reading beancount account from user input")?
+                // Convert Some("") to None
+                .filter(|line| !line.is_empty());
+
             let method = PaymentMethod {
                 name,
                 display_text,
                 url,
+                account,
             };
 
             Ok(Some(method))
@@ -50,6 +71,18 @@ impl PaymentMethod {
             Ok(None)
         }
     }
+
+    fn default_account(name: &str) -> String {
+        format!("Assets:{}", account_component(name))
+    }
+
+    /// The beancount account for this payment method, e.g. `Assets:PayPal`
+    /// for a method named "PayPal" without an explicit account.
+    pub fn beancount_account(&self) -> String {
+        self.account
+            .clone()
+            .unwrap_or_else(|| Self::default_account(&self.name))
+    }
 }
 
 impl Display for PaymentMethod {
@@ -59,15 +92,181 @@ impl Display for PaymentMethod {
     }
 }
 
+#[derive(Clone, Debug, Error)]
+pub enum HexColorError {
+    #[error("Color {0:?} must be a hex code such as 1F6FEB")]
+    Invalid(String),
+}
+
+/// An RGB color, written as a hex code such as `1F6FEB` or `#1F6FEB`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor(String);
+
+impl FromStr for HexColor {
+    type Err = HexColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(HexColorError::Invalid(s.to_owned()));
+        }
+
+        Ok(Self(hex.to_ascii_uppercase()))
+    }
+}
+
+impl TryFrom<String> for HexColor {
+    type Error = HexColorError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<HexColor> for String {
+    fn from(color: HexColor) -> Self {
+        color.0
+    }
+}
+
+impl Display for HexColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Images and colors shown on generated documents.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Branding {
+    /// PNG, JPEG or PDF image shown in the document header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<PathBuf>,
+    /// Color of the document title and date.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<HexColor>,
+    /// PNG, JPEG or PDF image of your signature, shown above your name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<PathBuf>,
+}
+
+impl Branding {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The logo and signature images, named `logo` and `signature` with their
+    /// original extension, so that templates can include them with e.g. `\includegraphics{logo}`.
+    pub fn assets(&self) -> anyhow::Result<Vec<Asset>> {
+        [("logo", &self.logo), ("signature", &self.signature)]
+            .into_iter()
+            .filter_map(|(name, path)| Some((name, path.as_ref()?)))
+            .map(|(name, path)| image_asset(name, path))
+            .collect()
+    }
+
+    /// The logo and signature as `data:` URIs, so that HTML documents stay self-contained.
+    /// PDF images are left out, since browsers don't show them in `<img>` tags.
+    pub fn html_images(&self) -> anyhow::Result<HtmlImages> {
+        let data_uri = |name: &str, path: &Option<PathBuf>| -> anyhow::Result<Option<String>> {
+            let Some(path) = path else {
+                return Ok(None);
+            };
+            let asset = image_asset(name, path)?;
+            let media_type = match asset.filename.rsplit_once('.') {
+                Some((_, "png")) => "image/png",
+                Some((_, "jpg" | "jpeg")) => "image/jpeg",
+                _ => return Ok(None),
+            };
+
+            Ok(Some(format!(
+                "data:{};base64,{}",
+                media_type,
+                base64_encode(&asset.data)
+            )))
+        };
+
+        Ok(HtmlImages {
+            logo: data_uri("logo", &self.logo)?,
+            signature: data_uri("signature", &self.signature)?,
+        })
+    }
+}
+
+/// Branding images embedded in HTML documents.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HtmlImages {
+    pub logo: Option<String>,
+    pub signature: Option<String>,
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (triple >> (18 - 6 * i)) & 0x3f;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+fn image_asset(name: &str, path: &PathBuf) -> anyhow::Result<Asset> {
+    let path = expand_tilde(path);
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let Some(extension) =
+        extension.filter(|extension| IMAGE_EXTENSIONS.contains(&extension.as_str()))
+    else {
+        bail!("{} {:?} must be a PNG, JPEG or PDF file", name, path);
+    };
+
+    let data = std::fs::read(&path).with_context(|| format!("reading {} {:?}", name, path))?;
+
+    Ok(Asset {
+        data,
+        filename: format!("{}.{}", name, extension),
+    })
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Me {
     pub name: String,
     pub address: MailingAddress,
     pub contact: ContactInfo,
     pub payment: Vec<PaymentMethod>,
+    #[serde(default, skip_serializing_if = "Branding::is_empty")]
+    pub branding: Branding,
 }
 
 impl Me {
+    /// The beancount account for the payment method with the given name.
+    pub fn payment_account(&self, method_name: &str) -> String {
+        self.payment
+            .iter()
+            .find(|method| method.name == method_name)
+            .map(PaymentMethod::beancount_account)
+            .unwrap_or_else(|| PaymentMethod::default_account(method_name))
+    }
+
     pub fn edit_yaml(&self) -> anyhow::Result<Self> {
         let yaml = serde_yaml::to_string(&self).context("serializing personal info yaml")?;
 
@@ -119,6 +318,7 @@ impl Me {
             address,
             contact,
             payment: payment_methods,
+            branding: Branding::default(),
         };
 
         me = me.edit_yaml().context("editing personal info yaml")?;
@@ -165,3 +365,77 @@ impl Me {
     //     Ok(())
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{base64_encode, Branding, HexColor, HtmlImages};
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(HexColor::from_str("#1f6feb").unwrap().to_string(), "1F6FEB");
+        assert_eq!(HexColor::from_str("1F6FEB").unwrap().to_string(), "1F6FEB");
+        assert!(HexColor::from_str("blue").is_err());
+        assert!(HexColor::from_str("#1F6FE").is_err());
+    }
+
+    #[test]
+    fn test_branding_assets() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let logo = dir.path().join("Company Logo.PNG");
+        std::fs::write(&logo, [0x89, b'P', b'N', b'G'])?;
+
+        let branding = Branding {
+            logo: Some(logo),
+            accent_color: None,
+            signature: None,
+        };
+        let assets = branding.assets()?;
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].filename, "logo.png");
+
+        let branding = Branding {
+            signature: Some(dir.path().join("signature.svg")),
+            ..Branding::default()
+        };
+        assert!(branding.assets().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(&[0xff, 0xfe]), "//4=");
+    }
+
+    #[test]
+    fn test_branding_html_images() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let logo = dir.path().join("logo.png");
+        std::fs::write(&logo, b"foo")?;
+        let signature = dir.path().join("signature.pdf");
+        std::fs::write(&signature, b"%PDF")?;
+
+        let branding = Branding {
+            logo: Some(logo),
+            accent_color: None,
+            signature: Some(signature),
+        };
+
+        assert_eq!(
+            branding.html_images()?,
+            HtmlImages {
+                logo: Some("data:image/png;base64,Zm9v".to_owned()),
+                signature: None,
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fmt::{self, Display},
+    iter::Sum,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use thiserror::Error;
+
+/// An exact amount of money.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Money(Decimal);
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    pub fn new(amount: Decimal) -> Self {
+        Self(amount)
+    }
+
+    pub fn as_decimal(&self) -> Decimal {
+        self.0
+    }
+
+    /// Round to whole cents, rounding halves away from zero.
+    /// The result always has exactly two decimal places.
+    pub fn round_to_cents(self) -> Self {
+        let mut amount = self
+            .0
+            .round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+        amount.rescale(2);
+
+        Self(amount)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2}", self.round_to_cents().0)
+    }
+}
+
+impl FromStr for Money {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let amount = Decimal::from_str(s.trim())?;
+        Ok(Self(amount))
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+impl Mul<Quantity> for Money {
+    type Output = Money;
+
+    fn mul(self, rhs: Quantity) -> Self::Output {
+        Self(self.0 * rhs.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_decimal(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor).map(Self)
+    }
+}
+
+/// An exact quantity (e.g. number of hours) of a line item.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Quantity(Decimal);
+
+impl Quantity {
+    pub fn new(amount: Decimal) -> Self {
+        Self(amount)
+    }
+
+    pub fn as_decimal(&self) -> Decimal {
+        self.0
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.normalize())
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let amount = Decimal::from_str(s.trim())?;
+        Ok(Self(amount))
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_decimal(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor).map(Self)
+    }
+}
+
+/// A percentage, e.g. a tax rate of `8.25` percent.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Percentage(Decimal);
+
+impl Percentage {
+    pub fn new(percent: Decimal) -> Self {
+        Self(percent)
+    }
+
+    pub fn as_decimal(&self) -> Decimal {
+        self.0
+    }
+
+    /// This percentage of the given amount, without rounding.
+    pub fn of(&self, amount: Money) -> Money {
+        Money(amount.0 * self.0 / Decimal::ONE_HUNDRED)
+    }
+}
+
+impl Display for Percentage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.normalize())
+    }
+}
+
+impl FromStr for Percentage {
+    type Err = rust_decimal::Error;
+
+    /// Parse a percentage, with or without a trailing `%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percent = Decimal::from_str(s.trim().trim_end_matches('%').trim_end())?;
+        Ok(Self(percent))
+    }
+}
+
+impl Serialize for Percentage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_decimal(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Percentage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor).map(Self)
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum CurrencyError {
+    #[error("Currency code {0:?} must consist of three letters")]
+    InvalidCode(String),
+}
+
+/// An ISO 4217 currency code, e.g. `USD` or `EUR`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct Currency(String);
+
+/// Currencies for which `CSMinimalInvoice.cls` knows a symbol.
+const LATEX_SYMBOL_CURRENCIES: &[&str] = &["USD", "GBP", "CNY", "JPY", "EUR", "BRL", "INR"];
+
+impl Currency {
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the LaTeX invoice class can display a symbol for this currency.
+    /// Other currencies are shown with the currency code as a suffix instead.
+    pub fn has_latex_symbol(&self) -> bool {
+        LATEX_SYMBOL_CURRENCIES.contains(&self.code())
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self("USD".to_owned())
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = CurrencyError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let code = s.trim().to_ascii_uppercase();
+
+        if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            Ok(Self(code))
+        } else {
+            Err(CurrencyError::InvalidCode(s))
+        }
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.0
+    }
+}
+
+impl FromStr for Currency {
+    type Err = CurrencyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.to_owned().try_into()
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Write a plain number whenever it reads back exactly, so that YAML files
+/// stay readable (and compatible with older versions), falling back to a string.
+fn serialize_decimal<S: Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+    match value.to_f64() {
+        Some(float) if Decimal::from_str(&float.to_string()).ok() == Some(*value) => {
+            serializer.serialize_f64(float)
+        }
+        _ => serializer.serialize_str(&value.to_string()),
+    }
+}
+
+/// Write amounts as strings with exactly two decimals, as printed,
+/// so that JSON consumers and templates never see binary floats.
+pub fn serialize_amount<S: Serializer>(amount: &Money, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&amount.to_string())
+}
+
+/// Accept integers, floats and strings, since older files store amounts as floats.
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal number")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        // Go through the shortest string representation of the float
+        // to recover the number that was actually written in the file.
+        Decimal::from_str(&v.to_string()).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Decimal::from_str(v.trim()).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Currency, Money, Percentage, Quantity};
+
+    #[test]
+    fn test_deserialize_legacy_floats() -> anyhow::Result<()> {
+        let money: Money = serde_yaml::from_str("10.3")?;
+        let quantity: Quantity = serde_yaml::from_str("1.0")?;
+        let quoted: Money = serde_yaml::from_str("'1234567890123456.78'")?;
+
+        assert_eq!(money, Money::from_str("10.30")?);
+        assert_eq!(quantity, Quantity::from_str("1")?);
+        assert_eq!(quoted, Money::from_str("1234567890123456.78")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() -> anyhow::Result<()> {
+        for s in ["10.3", "0.1", "1234567890123456.78"] {
+            let money = Money::from_str(s)?;
+            let yaml = serde_yaml::to_string(&money)?;
+            let parsed: Money = serde_yaml::from_str(&yaml)?;
+
+            assert_eq!(parsed, money);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_currency() -> anyhow::Result<()> {
+        assert_eq!(Currency::from_str("eur")?.code(), "EUR");
+        assert!(Currency::from_str("EURO").is_err());
+        assert!(Currency::from_str("US$").is_err());
+
+        let currency: Currency = serde_yaml::from_str("GBP")?;
+        assert!(currency.has_latex_symbol());
+        assert!(!Currency::from_str("CHF")?.has_latex_symbol());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exact_totals() -> anyhow::Result<()> {
+        let price = Money::from_str("0.10")?;
+        let quantity = Quantity::from_str("1")?;
+        let total: Money = (0..30).map(|_| price * quantity).sum();
+
+        assert_eq!(total, Money::from_str("3")?);
+        assert_eq!(total.to_string(), "3.00");
+        assert_eq!(Money::from_str("2.675")?.to_string(), "2.68");
+
+        let tax = Percentage::from_str("8.25%")?.of(Money::from_str("19.99")?);
+        assert_eq!(tax.round_to_cents(), Money::from_str("1.65")?);
+
+        Ok(())
+    }
+}
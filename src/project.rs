@@ -1,5 +1,6 @@
 use std::fs::File;
 
+use anyhow::{bail, Context};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -8,7 +9,9 @@ use crate::{
     completion::{LocalAutocompleter, PrefixAutocomplete},
     config::Config,
     id::Id,
-    storage::get_projects_dir,
+    money::{Currency, Money, Percentage},
+    references::ProjectReferences,
+    storage::{find_client, find_project, get_projects_dir},
 };
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -17,6 +20,16 @@ pub struct Project {
     pub name: Id,
     pub description: String,
     pub client_ref: Id,
+    /// Currency used for this project's invoices, overriding the client's.
+    pub currency: Option<Currency>,
+    /// Unit price for new line items, overriding the client's.
+    pub hourly_rate: Option<Money>,
+    /// Days after the invoice date that payment is due, overriding the client's.
+    pub days_to_pay: Option<u16>,
+    /// Terms and conditions for new invoices, overriding the client's.
+    pub conditions: Option<String>,
+    /// Template set used for this project's documents, overriding the client's.
+    pub template: Option<String>,
 }
 
 impl Project {
@@ -74,6 +87,11 @@ impl Project {
             name,
             description,
             client_ref: client_name,
+            currency: None,
+            hourly_rate: None,
+            days_to_pay: None,
+            conditions: None,
+            template: None,
         };
 
         project = project.edit_yaml()?;
@@ -81,6 +99,62 @@ impl Project {
         Ok(project)
     }
 
+    /// The currency to use for new invoices for this project,
+    /// falling back to the client's currency and then USD.
+    pub fn default_currency(&self, config: &Config) -> anyhow::Result<Currency> {
+        if let Some(currency) = &self.currency {
+            return Ok(currency.clone());
+        }
+
+        let client = find_client(&self.client_ref, config).context("finding client")?;
+        let currency = client.currency.unwrap_or_default();
+
+        Ok(currency)
+    }
+
+    /// The tax rate to use for new invoices for this project,
+    /// taken from the client or else from `config.toml`.
+    pub fn default_tax_rate(&self, config: &Config) -> anyhow::Result<Option<Percentage>> {
+        let client = find_client(&self.client_ref, config).context("finding client")?;
+        let tax_rate = client.tax_rate.or(config.invoice.tax_rate);
+
+        Ok(tax_rate)
+    }
+
+    /// The unit price for new line items, falling back to the client's hourly rate.
+    pub fn default_hourly_rate(&self, config: &Config) -> anyhow::Result<Option<Money>> {
+        if self.hourly_rate.is_some() {
+            return Ok(self.hourly_rate);
+        }
+
+        let client = find_client(&self.client_ref, config).context("finding client")?;
+
+        Ok(client.hourly_rate)
+    }
+
+    /// The number of days to pay new invoices,
+    /// falling back to the client's and then 7 days.
+    pub fn default_days_to_pay(&self, config: &Config) -> anyhow::Result<u16> {
+        if let Some(days_to_pay) = self.days_to_pay {
+            return Ok(days_to_pay);
+        }
+
+        let client = find_client(&self.client_ref, config).context("finding client")?;
+
+        Ok(client.days_to_pay.unwrap_or(7))
+    }
+
+    /// The terms and conditions for new invoices, falling back to the client's.
+    pub fn default_conditions(&self, config: &Config) -> anyhow::Result<Option<String>> {
+        if let Some(conditions) = &self.conditions {
+            return Ok(Some(conditions.clone()));
+        }
+
+        let client = find_client(&self.client_ref, config).context("finding client")?;
+
+        Ok(client.conditions)
+    }
+
     pub fn filename(&self) -> String {
         self.name.to_filename()
     }
@@ -95,6 +169,64 @@ impl Project {
         Ok(())
     }
 
+    /// Rename a project, updating every document which refers to it.
+    pub fn rename(old: &Id, new: Id, config: &Config) -> anyhow::Result<()> {
+        let mut project = find_project(old, config).context("finding project")?;
+        if Self::list(config)?
+            .iter()
+            .any(|name| name != old && name.eq_ignore_case(&new))
+        {
+            bail!("a project named {} already exists", new);
+        }
+
+        // Update the references while the old file still exists,
+        // so that running the rename again finishes it after a failure.
+        let references = ProjectReferences::find(old, config).context("finding references")?;
+        references
+            .rename(old, &new, config)
+            .context("updating references (rename again to update the rest)")?;
+
+        // Move rather than copy the file, since `web.yaml` and `Web.yaml`
+        // are the same file on case-insensitive filesystems.
+        let projects_dir = get_projects_dir(config)?;
+        std::fs::rename(
+            projects_dir.join(old.to_filename()),
+            projects_dir.join(new.to_filename()),
+        )
+        .context("renaming project file")?;
+        project.name = new;
+        project.save(config).context("saving renamed project")?;
+
+        Ok(())
+    }
+
+    /// Delete a project. Fails if anything still refers to it,
+    /// unless `force` is given, which deletes those documents as well.
+    pub fn delete(name: &Id, force: bool, config: &Config) -> anyhow::Result<()> {
+        find_project(name, config).context("finding project")?;
+
+        let references = ProjectReferences::find(name, config).context("finding references")?;
+        if !references.is_empty() {
+            if !force {
+                bail!(
+                    "project {} is still referenced by {} (use --force to delete them too)",
+                    name,
+                    references.describe()
+                );
+            }
+
+            references
+                .delete(name, config)
+                .context("deleting references")?;
+        }
+
+        let projects_dir = get_projects_dir(config)?;
+        std::fs::remove_file(projects_dir.join(name.to_filename()))
+            .context("removing project file")?;
+
+        Ok(())
+    }
+
     pub fn list(config: &Config) -> anyhow::Result<Vec<Id>> {
         let projects_dir = get_projects_dir(config)?;
 
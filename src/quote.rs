@@ -0,0 +1,408 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration};
+
+use crate::{
+    cli::{print_header, YamlValidator},
+    client::Client,
+    config::Config,
+    date::DateString,
+    discount::Discount,
+    id::Id,
+    invoice::{FormattedTotals, Invoice, LineItem, Totals},
+    latex::{Document, Latex},
+    me::Me,
+    money::{Currency, Percentage},
+    project::Project,
+    storage::{find_client, find_project, get_pdfs_dir, get_quotes_dir},
+};
+
+/// An estimate sent before an engagement, which can later be converted into an invoice.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quote {
+    pub number: u32,
+    pub project_ref: Id,
+    pub date: DateString,
+    /// Date after which the quote expires.
+    pub valid_until: DateString,
+    #[serde(default)]
+    pub currency: Currency,
+    /// Tax rate (in percent) for line items which don't specify their own.
+    #[serde(default)]
+    pub tax_rate: Option<Percentage>,
+    pub items: Vec<LineItem>,
+    #[serde(default)]
+    pub discounts: Vec<Discount>,
+    pub conditions: Option<String>,
+    /// The invoice this quote was converted into, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoice_num: Option<u32>,
+}
+
+impl Quote {
+    pub fn list(config: &Config) -> anyhow::Result<Vec<u32>> {
+        let quotes_dir = get_quotes_dir(config).context("getting quotes directory")?;
+
+        let quote_numbers: Vec<u32> = quotes_dir
+            .read_dir()
+            .context("listing quote files")?
+            .filter_map(|entry_res| {
+                let entry = entry_res.ok()?;
+                let path = entry.path();
+                let stem = path.file_stem()?.to_string_lossy();
+                let number = stem.parse().ok()?;
+
+                Some(number)
+            })
+            .collect();
+
+        Ok(quote_numbers)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref()).context("reading quote file")?;
+        let quote: Quote = serde_yaml::from_reader(file).context("parsing quote yaml")?;
+
+        Ok(quote)
+    }
+
+    pub fn load(number: u32, config: &Config) -> anyhow::Result<Self> {
+        let quotes_dir = get_quotes_dir(config).context("getting quotes directory")?;
+        let filename = format!("{}.yaml", number);
+        let path = quotes_dir.join(filename);
+        let quote = Quote::load_from_path(path).context("loading quote from file")?;
+
+        Ok(quote)
+    }
+
+    pub fn get_next_number(config: &Config) -> anyhow::Result<u32> {
+        let existing_numbers = Self::list(config).context("listing quotes")?;
+        let max = existing_numbers.iter().fold(0, |acc, &el| acc.max(el));
+
+        Ok(max + 1)
+    }
+
+    pub fn edit_yaml(&self) -> anyhow::Result<Self> {
+        let yaml = serde_yaml::to_string(&self).context("serializing quote")?;
+
+        print_header("Final YAML");
+        println!("{}", yaml);
+
+        let yaml_validator = YamlValidator::<Quote>::new();
+
+        let edited = inquire::Editor::new("Edit...")
+            .with_predefined_text(&yaml)
+            .with_validator(yaml_validator)
+            .with_file_extension(".yaml")
+            .prompt()?;
+
+        let parsed = serde_yaml::from_str(&edited).context("parsing edited quote yaml")?;
+
+        Ok(parsed)
+    }
+
+    pub fn create_from_user_input(config: &Config) -> anyhow::Result<Self> {
+        let number = Self::get_next_number(config).context("getting next quote number")?;
+
+        print_header(&format!("Create quote {}", number));
+
+        let project_name = Project::get_or_create_from_user_input(config)
+            .context("getting or creating project")?;
+        let project = find_project(&project_name, config).context("finding project")?;
+
+        let chrono_date = inquire::DateSelect::new("Quote date:")
+            .prompt()
+            .context("reading quote date from user input")?;
+        // Convert `chrono::Date` to `time::Date`.
+        let date_string = DateString::try_new(chrono_date.to_string())
+            .context("parsing quote DateString from user input")?;
+        let date: Date = date_string
+            .clone()
+            .try_into()
+            .context("parsing quote Date from user input")?;
+
+        let days_valid = inquire::CustomType::<u16>::new("Days valid:")
+            .with_default(30)
+            .prompt()
+            .context("reading days valid from user input")?;
+        let valid_until = date + Duration::days(days_valid.into());
+
+        let default_currency = project
+            .default_currency(config)
+            .context("getting default currency")?;
+        let currency = inquire::CustomType::<Currency>::new("Currency:")
+            .with_default(default_currency)
+            .prompt()
+            .context("reading currency from user input")?;
+
+        let default_tax_rate = project
+            .default_tax_rate(config)
+            .context("getting default tax rate")?
+            .map(|rate| rate.to_string())
+            .unwrap_or_default();
+        let tax_rate = inquire::Text::new("Tax rate (%):")
+            .with_initial_value(&default_tax_rate)
+            .with_help_message("Leave empty for no tax")
+            .prompt_skippable()
+            .context("reading tax rate from user input")?
+            // Convert Some("") to None
+            .filter(|line| !line.is_empty())
+            .map(|line| line.parse::<Percentage>())
+            .transpose()
+            .context("parsing tax rate")?;
+
+        let default_hourly_rate = project
+            .default_hourly_rate(config)
+            .context("getting default hourly rate")?;
+
+        let mut items = Vec::new();
+
+        while let Some(item) = LineItem::create_from_user_input(default_hourly_rate)
+            .context("creating line item from user input")?
+        {
+            items.push(item);
+        }
+
+        let mut discounts = Vec::new();
+
+        while let Some(discount) =
+            Discount::create_from_user_input().context("creating discount from user input")?
+        {
+            discounts.push(discount);
+        }
+
+        let default_conditions = project
+            .default_conditions(config)
+            .context("getting default conditions")?
+            .unwrap_or_default();
+        let conditions = inquire::Text::new("Terms and conditions:")
+            .with_initial_value(&default_conditions)
+            .prompt_skippable()
+            .context("reading conditions from user input")?
+            // Convert Some("") to None
+            .filter(|line| !line.is_empty());
+
+        let mut quote = Quote {
+            number,
+            project_ref: project_name,
+            date: date_string,
+            valid_until: valid_until.into(),
+            currency,
+            tax_rate,
+            items,
+            discounts,
+            conditions,
+            invoice_num: None,
+        };
+
+        quote = quote.edit_yaml().context("editing quote yaml")?;
+
+        Ok(quote)
+    }
+
+    pub fn totals(&self) -> Totals {
+        Totals::compute(&self.items, &self.discounts, self.tax_rate)
+    }
+
+    /// Whether the quote is no longer valid on the given date.
+    pub fn is_expired(&self, date: Date) -> anyhow::Result<bool> {
+        let valid_until: Date = self
+            .valid_until
+            .clone()
+            .try_into()
+            .context("parsing quote validity date")?;
+
+        Ok(date > valid_until)
+    }
+
+    /// Create an invoice with this quote's project, items and terms.
+    pub fn to_invoice(&self, number: u32, date: Date, days_to_pay: u16) -> anyhow::Result<Invoice> {
+        if let Some(invoice_num) = self.invoice_num {
+            bail!(
+                "quote {} was already converted into invoice {}",
+                self.number,
+                invoice_num
+            );
+        }
+
+        let due_date = date + Duration::days(days_to_pay.into());
+
+        let invoice = Invoice {
+            number,
+            project_ref: self.project_ref.clone(),
+            date: date.into(),
+            due_date: due_date.into(),
+            currency: self.currency.clone(),
+            tax_rate: self.tax_rate,
+            items: self.items.clone(),
+            expenses: Vec::new(),
+            discounts: self.discounts.clone(),
+            conditions: self.conditions.clone(),
+        };
+
+        Ok(invoice)
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}.yaml", self.number)
+    }
+
+    pub fn save(&self, config: &Config) -> anyhow::Result<()> {
+        let quotes_dir = get_quotes_dir(config).context("getting quotes directory")?;
+        let path = quotes_dir.join(self.filename());
+        let file = File::create(path).context("opening quote output file")?;
+
+        serde_yaml::to_writer(file, self).context("serializing quote yaml")?;
+
+        Ok(())
+    }
+
+    pub fn collect(self, config: &Config) -> anyhow::Result<FullQuote> {
+        let project = find_project(&self.project_ref, config).context("finding project")?;
+        let client = find_client(&project.client_ref, config).context("finding client")?;
+
+        let full_quote = FullQuote {
+            me: config.me.clone(),
+            quote: self,
+            project,
+            client,
+        };
+        Ok(full_quote)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Template)]
+#[serde(deny_unknown_fields)]
+#[template(path = "quote.tex")]
+pub struct FullQuote {
+    pub me: Me,
+    pub quote: Quote,
+    pub project: Project,
+    pub client: Client,
+}
+
+/// A full quote with the values the built-in template computes,
+/// for rendering user-supplied templates.
+#[derive(Serialize)]
+pub struct QuoteTemplateContext<'a> {
+    #[serde(flatten)]
+    pub full_quote: &'a FullQuote,
+    pub totals: Totals,
+    /// The totals formatted as printed, e.g. `29.50`.
+    pub formatted: FormattedTotals,
+    pub has_latex_symbol: bool,
+}
+
+impl FullQuote {
+    pub fn filename(&self) -> String {
+        let name_no_whitespace = self.me.name.split_whitespace().collect::<Vec<_>>().join("");
+
+        format!("Quote_{}_{}.pdf", name_no_whitespace, self.quote.number)
+    }
+
+    /// The data available to user-supplied templates.
+    pub fn template_context(&self) -> QuoteTemplateContext<'_> {
+        let totals = self.quote.totals();
+
+        QuoteTemplateContext {
+            full_quote: self,
+            formatted: FormattedTotals::new(&self.quote.items, &[], &totals),
+            totals,
+            has_latex_symbol: self.quote.currency.has_latex_symbol(),
+        }
+    }
+
+    fn render_pdf(
+        &self,
+        pdf_output_path: impl AsRef<Path>,
+        config: &Config,
+        show_tex: bool,
+    ) -> anyhow::Result<()> {
+        let context = self.template_context();
+        let document = Document {
+            name: "quote",
+            latex: self,
+            context: &context,
+            me: &self.me,
+            project: &self.project,
+            client: &self.client,
+        };
+
+        document
+            .render_pdf(pdf_output_path.as_ref(), config, show_tex)
+            .context("rendering quote PDF")
+    }
+
+    pub fn save_pdf(&self, config: &Config, show_tex: bool) -> anyhow::Result<PathBuf> {
+        let pdfs_dir = get_pdfs_dir(config).context("getting pdfs directory")?;
+        let path = pdfs_dir.join(self.filename());
+
+        self.render_pdf(&path, config, show_tex)
+            .context("generating quote PDF")?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use crate::{date::DateString, test_util::create_full_test_invoice};
+
+    use super::Quote;
+
+    fn create_test_quote() -> Quote {
+        let invoice = create_full_test_invoice().invoice;
+
+        Quote {
+            number: 3,
+            project_ref: invoice.project_ref,
+            date: DateString::try_new("2022-12-20".to_owned()).unwrap(),
+            valid_until: DateString::try_new("2023-01-19".to_owned()).unwrap(),
+            currency: invoice.currency,
+            tax_rate: invoice.tax_rate,
+            items: invoice.items,
+            discounts: invoice.discounts,
+            conditions: invoice.conditions,
+            invoice_num: None,
+        }
+    }
+
+    #[test]
+    fn test_to_invoice() -> anyhow::Result<()> {
+        let quote = create_test_quote();
+        let expected = create_full_test_invoice().invoice;
+
+        let invoice = quote.to_invoice(17, date!(2023 - 01 - 07), 14)?;
+
+        assert_eq!(invoice, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_invoice_twice() {
+        let mut quote = create_test_quote();
+        quote.invoice_num = Some(17);
+
+        assert!(quote.to_invoice(18, date!(2023 - 01 - 07), 14).is_err());
+    }
+
+    #[test]
+    fn test_is_expired() -> anyhow::Result<()> {
+        let quote = create_test_quote();
+
+        assert!(!quote.is_expired(date!(2023 - 01 - 19))?);
+        assert!(quote.is_expired(date!(2023 - 01 - 20))?);
+
+        Ok(())
+    }
+}
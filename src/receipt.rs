@@ -1,57 +1,143 @@
 use std::{
-    collections::HashSet,
     fmt::Display,
     fs::File,
+    io::Write,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use anyhow::{bail, Context};
 use askama::Template;
+use beancount_core::{Amount, Posting};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::Date;
 
 use crate::{
-    cli::{print_header, YamlValidator},
+    beancount::{rebuild_index, ConfiguredTransaction, Placeholders},
+    cli::{print_header, SelectOption, YamlValidator},
     client::Client,
-    config::Config,
+    config::{BeancountConfig, Config},
     date::DateString,
-    invoice::Invoice,
-    latex::{compile_latex, Asset, Latex},
-    me::Me,
+    invoice::{FormattedTotals, Invoice, Totals},
+    latex::{Document, Latex},
+    me::{HtmlImages, Me},
+    money::{serialize_amount, Money},
     project::Project,
-    storage::{find_client, find_invoice, find_project, get_pdfs_dir, get_receipts_dir},
+    storage::{
+        find_client, find_invoice, find_project, get_beancount_dir, get_html_dir, get_pdfs_dir,
+        get_receipts_dir,
+    },
 };
 
-struct SelectOption<T> {
-    pub value: T,
-    pub description: String,
+#[derive(Clone, Debug, Error)]
+pub enum ReceiptNumberError {
+    #[error("Receipt number {0:?} must have the form INVOICE or INVOICE-PAYMENT")]
+    Invalid(String),
 }
 
-impl<T> Display for SelectOption<T> {
+/// Identifies a receipt by the invoice it pays and which payment towards
+/// that invoice it is, written e.g. `17` for the first payment and `17-2`
+/// for the second.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ReceiptNumber {
+    pub invoice_num: u32,
+    pub payment_num: u32,
+}
+
+impl Display for ReceiptNumber {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.description)
+        if self.payment_num == 1 {
+            write!(f, "{}", self.invoice_num)
+        } else {
+            write!(f, "{}-{}", self.invoice_num, self.payment_num)
+        }
     }
 }
 
+impl FromStr for ReceiptNumber {
+    type Err = ReceiptNumberError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ReceiptNumberError::Invalid(s.to_owned());
+
+        let (invoice_num, payment_num) = match s.split_once('-') {
+            Some((invoice, payment)) => (invoice.parse(), payment.parse()),
+            None => (s.parse(), Ok(1)),
+        };
+
+        let number = Self {
+            invoice_num: invoice_num.map_err(|_| invalid())?,
+            payment_num: payment_num.map_err(|_| invalid())?,
+        };
+
+        if number.payment_num == 0 {
+            return Err(invalid());
+        }
+
+        Ok(number)
+    }
+}
+
+fn first_payment() -> u32 {
+    1
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Receipt {
     pub invoice_num: u32,
+    /// Which payment towards the invoice this receipt is for, starting from 1.
+    #[serde(default = "first_payment")]
+    pub payment_num: u32,
     pub date: DateString,
     pub payment_method: String,
+    /// Amount paid. Receipts without an amount paid off the remaining balance.
+    #[serde(default)]
+    pub amount: Option<Money>,
+}
+
+/// The amount paid by each of a list of receipts for the same invoice, in payment order.
+pub fn amounts_paid<'a>(
+    receipts: impl IntoIterator<Item = &'a Receipt>,
+    total: Money,
+) -> Vec<Money> {
+    let mut paid = Money::ZERO;
+
+    receipts
+        .into_iter()
+        .map(|receipt| {
+            let amount = receipt.amount.unwrap_or(total - paid);
+            paid = paid + amount;
+            amount
+        })
+        .collect()
+}
+
+/// The total paid by a list of receipts for the same invoice, in payment order.
+pub fn amount_paid<'a>(receipts: impl IntoIterator<Item = &'a Receipt>, total: Money) -> Money {
+    amounts_paid(receipts, total).into_iter().sum()
 }
 
 impl Receipt {
-    pub fn list(config: &Config) -> anyhow::Result<Vec<u32>> {
+    pub fn number(&self) -> ReceiptNumber {
+        ReceiptNumber {
+            invoice_num: self.invoice_num,
+            payment_num: self.payment_num,
+        }
+    }
+
+    pub fn list(config: &Config) -> anyhow::Result<Vec<ReceiptNumber>> {
         let receipts_dir = get_receipts_dir(config).context("getting receipts directory")?;
 
-        let receipt_numbers: Vec<u32> = receipts_dir
+        let receipt_numbers: Vec<ReceiptNumber> = receipts_dir
             .read_dir()
             .context("listing files in receipts directory")?
             .filter_map(|entry_res| {
                 let entry = entry_res.ok()?;
                 let path = entry.path();
                 let stem = path.file_stem()?.to_string_lossy();
-                let number = u32::from_str_radix(&stem, 10).ok()?;
+                let number = stem.parse().ok()?;
 
                 Some(number)
             })
@@ -60,6 +146,43 @@ impl Receipt {
         Ok(receipt_numbers)
     }
 
+    pub fn exists(number: ReceiptNumber, config: &Config) -> anyhow::Result<bool> {
+        Self::list(config).map(|numbers| numbers.contains(&number))
+    }
+
+    /// Load all receipts for an invoice, in payment order.
+    pub fn list_for_invoice(invoice_num: u32, config: &Config) -> anyhow::Result<Vec<Self>> {
+        let mut numbers: Vec<ReceiptNumber> = Self::list(config)
+            .context("listing receipts")?
+            .into_iter()
+            .filter(|number| number.invoice_num == invoice_num)
+            .collect();
+        numbers.sort();
+
+        numbers
+            .into_iter()
+            .map(|number| {
+                Self::load(number, config).with_context(|| format!("loading receipt {}", number))
+            })
+            .collect()
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref()).context("opening receipt file")?;
+        let receipt: Receipt = serde_yaml::from_reader(file).context("parsing receipt yaml")?;
+
+        Ok(receipt)
+    }
+
+    pub fn load(number: ReceiptNumber, config: &Config) -> anyhow::Result<Self> {
+        let receipts_dir = get_receipts_dir(config).context("getting receipts directory")?;
+        let filename = format!("{}.yaml", number);
+        let path = receipts_dir.join(filename);
+        let receipt = Receipt::load_from_path(path).context("loading receipt from file")?;
+
+        Ok(receipt)
+    }
+
     pub fn edit_yaml(&self) -> anyhow::Result<Self> {
         let yaml = serde_yaml::to_string(&self).context("serializing receipt yaml")?;
 
@@ -82,37 +205,27 @@ impl Receipt {
     }
 
     pub fn create_from_user_input(config: &Config) -> anyhow::Result<Self> {
-        let invoice_nums = Invoice::list(config)
-            .context("listing invoices")?
-            .into_iter()
-            .collect::<HashSet<_>>();
-        let receipt_nums = Receipt::list(config)
-            .context("listing receipts")?
-            .into_iter()
-            .collect::<HashSet<_>>();
+        let invoice_nums = Invoice::list(config).context("listing invoices")?;
 
-        let unpaid_invoice_nums = &invoice_nums - &receipt_nums;
-
-        if unpaid_invoice_nums.len() == 0 {
-            bail!("All invoices have been paid!");
-        }
-
-        let mut invoice_options: Vec<SelectOption<u32>> = unpaid_invoice_nums
+        let mut invoice_options: Vec<SelectOption<(u32, Money)>> = invoice_nums
             .into_iter()
-            // // Load invoice from disk
-            // .map(Invoice::load)
-            // // Silently discard any invoices that
-            // // could not be read sucessfully.
-            // .filter_map(Result::ok)
             .filter_map(|number| {
+                // Silently discard any invoices that
+                // could not be read sucessfully.
                 let inv = Invoice::load(number, config).ok()?;
+                let balance = inv.outstanding_balance(config).ok()?;
+
+                if balance <= Money::ZERO {
+                    return None;
+                }
+
                 let description = format!(
-                    "#{} on {} (due {}) for {}",
-                    inv.number, inv.date, inv.due_date, inv.project_ref
+                    "#{} on {} (due {}) for {}, {} {} outstanding",
+                    inv.number, inv.date, inv.due_date, inv.project_ref, balance, inv.currency
                 );
 
                 let choice = SelectOption {
-                    value: number,
+                    value: (number, balance),
                     description,
                 };
 
@@ -120,17 +233,31 @@ impl Receipt {
             })
             .collect();
 
-        invoice_options.sort_by_key(|opt| u32::MAX - opt.value);
+        if invoice_options.is_empty() {
+            bail!("All invoices have been paid!");
+        }
+
+        invoice_options.sort_by_key(|opt| u32::MAX - opt.value.0);
 
         let invoice_choice = inquire::Select::new("Invoice number:", invoice_options)
             .prompt()
             .context("reading invoice number for receipt from user input")?;
 
-        let invoice_num = invoice_choice.value;
+        let (invoice_num, balance) = invoice_choice.value;
 
-        // TODO: Edit receipt if number already exists
+        let payment_num = Self::list_for_invoice(invoice_num, config)
+            .context("listing previous receipts")?
+            .iter()
+            .map(|receipt| receipt.payment_num)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let number = ReceiptNumber {
+            invoice_num,
+            payment_num,
+        };
 
-        print_header(&format!("Create receipt {}", invoice_num));
+        print_header(&format!("Create receipt {}", number));
 
         let chrono_date = inquire::DateSelect::new("Receipt date:")
             .prompt()
@@ -139,6 +266,12 @@ impl Receipt {
         let date_string = DateString::try_new(chrono_date.to_string())
             .context("parsing invoice DateString from user input")?;
 
+        let amount = inquire::CustomType::<Money>::new("Amount paid:")
+            .with_default(balance)
+            .with_help_message("Defaults to the outstanding balance")
+            .prompt()
+            .context("reading amount paid from user input")?;
+
         let payment_options = config
             .me
             .payment
@@ -157,17 +290,49 @@ impl Receipt {
 
         let mut receipt = Receipt {
             invoice_num,
+            payment_num,
             date: date_string,
             payment_method,
+            amount: Some(amount),
         };
 
         receipt = receipt.edit_yaml().context("editing receipt yaml")?;
+        receipt.validate(config).context("validating receipt")?;
 
         Ok(receipt)
     }
 
+    /// Check that the receipt number is still free (it may have been edited)
+    /// and that the receipt doesn't pay more than the invoice's outstanding balance.
+    pub fn validate(&self, config: &Config) -> anyhow::Result<()> {
+        if Self::exists(self.number(), config)? {
+            bail!("receipt {} already exists", self.number());
+        }
+
+        let invoice = find_invoice(self.invoice_num, config).context("finding invoice")?;
+        let balance = invoice
+            .outstanding_balance(config)
+            .context("getting outstanding balance")?;
+
+        match self.amount {
+            Some(amount) if amount <= Money::ZERO => {
+                bail!("amount paid must be positive (found {})", amount)
+            }
+            Some(amount) if amount > balance => bail!(
+                "amount paid {} exceeds the outstanding balance of {} on invoice {}",
+                amount,
+                balance,
+                self.invoice_num
+            ),
+            None if balance <= Money::ZERO => {
+                bail!("invoice {} has already been paid", self.invoice_num)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn filename(&self) -> String {
-        format!("{}.yaml", self.invoice_num)
+        format!("{}.yaml", self.number())
     }
 
     pub fn save(&self, config: &Config) -> anyhow::Result<()> {
@@ -185,12 +350,33 @@ impl Receipt {
         let project = find_project(&invoice.project_ref, config).context("finding project")?;
         let client = find_client(&project.client_ref, config).context("finding client")?;
 
+        // Later credit notes don't change what an earlier receipt paid
+        let date: Date = self
+            .date
+            .clone()
+            .try_into()
+            .context("parsing receipt date")?;
+        let total = invoice
+            .amount_due_as_of(date, config)
+            .context("getting amount due after credit notes")?;
+        let previous_receipts: Vec<Receipt> = Self::list_for_invoice(self.invoice_num, config)
+            .context("listing receipts for invoice")?
+            .into_iter()
+            .filter(|receipt| receipt.payment_num < self.payment_num)
+            .collect();
+        let mut amounts = amounts_paid(previous_receipts.iter().chain([&self]), total);
+        let amount_paid = amounts.pop().expect("amounts should include this receipt");
+        let previously_paid: Money = amounts.into_iter().sum();
+        let balance = total - previously_paid - amount_paid;
+
         let full_receipt = FullReceipt {
             me: config.me.clone(),
             receipt: self,
             invoice,
             project,
             client,
+            amount_paid,
+            balance,
         };
         Ok(full_receipt)
     }
@@ -205,44 +391,326 @@ pub struct FullReceipt {
     pub invoice: Invoice,
     pub project: Project,
     pub client: Client,
+    /// The amount paid with this receipt.
+    #[serde(serialize_with = "serialize_amount")]
+    pub amount_paid: Money,
+    /// The amount still owed after this payment.
+    #[serde(serialize_with = "serialize_amount")]
+    pub balance: Money,
+}
+
+/// A full receipt with the values the built-in template computes,
+/// for rendering user-supplied templates.
+#[derive(Serialize)]
+pub struct ReceiptTemplateContext<'a> {
+    #[serde(flatten)]
+    pub full_receipt: &'a FullReceipt,
+    /// Totals of the invoice paid.
+    pub totals: Totals,
+    /// The totals formatted as printed, e.g. `29.50`.
+    pub formatted: FormattedTotals,
+    /// Whether this payment paid off the invoice.
+    pub paid_in_full: bool,
+    pub has_latex_symbol: bool,
+}
+
+/// A receipt as a single HTML page with inline CSS.
+#[derive(Template)]
+#[template(path = "receipt.html")]
+pub struct ReceiptHtml<'a> {
+    pub me: &'a Me,
+    pub images: HtmlImages,
+    pub receipt: &'a Receipt,
+    pub invoice: &'a Invoice,
+    pub project: &'a Project,
+    pub client: &'a Client,
+    pub amount_paid: Money,
+    pub balance: Money,
+    /// Totals of the invoice paid.
+    pub totals: Totals,
 }
 
 impl FullReceipt {
-    pub fn filename(&self) -> String {
+    fn file_stem(&self) -> String {
         let name_no_whitespace = self.me.name.split_whitespace().collect::<Vec<_>>().join("");
 
-        format!("Receipt_{}_{}.pdf", name_no_whitespace, self.invoice.number)
+        format!("Receipt_{}_{}", name_no_whitespace, self.receipt.number())
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}.pdf", self.file_stem())
+    }
+
+    pub fn html_filename(&self) -> String {
+        format!("{}.html", self.file_stem())
+    }
+
+    /// The data available to user-supplied templates.
+    pub fn template_context(&self) -> ReceiptTemplateContext<'_> {
+        let totals = self.invoice.totals();
+
+        ReceiptTemplateContext {
+            full_receipt: self,
+            formatted: FormattedTotals::new(&self.invoice.items, &self.invoice.expenses, &totals),
+            paid_in_full: self.balance.is_zero(),
+            totals,
+            has_latex_symbol: self.invoice.currency.has_latex_symbol(),
+        }
     }
 
     pub fn render_pdf(
         &self,
         pdf_output_path: impl AsRef<Path>,
+        config: &Config,
         show_tex: bool,
     ) -> anyhow::Result<()> {
-        let rendered_tex = Template::render(self).context("rendering receipt template")?;
-
-        if show_tex {
-            println!("Final LaTeX:\n\n{}", &rendered_tex);
-        }
-
-        let invoice_class = Asset {
-            data: include_bytes!("../assets/CSMinimalInvoice.cls").to_vec(),
-            filename: "CSMinimalInvoice.cls".to_owned(),
+        let context = self.template_context();
+        let document = Document {
+            name: "receipt",
+            latex: self,
+            context: &context,
+            me: &self.me,
+            project: &self.project,
+            client: &self.client,
         };
-        let assets = &[invoice_class];
-        compile_latex(&rendered_tex, pdf_output_path.as_ref(), assets)
-            .context("compiling receipt LaTeX to PDF")?;
 
-        Ok(())
+        document
+            .render_pdf(pdf_output_path.as_ref(), config, show_tex)
+            .context("rendering receipt PDF")
     }
 
     pub fn save_pdf(&self, config: &Config, show_tex: bool) -> anyhow::Result<PathBuf> {
         let pdfs_dir = get_pdfs_dir(config).context("getting receipts directory")?;
         let path = pdfs_dir.join(self.filename());
 
-        self.render_pdf(&path, show_tex)
+        self.render_pdf(&path, config, show_tex)
             .context("generating receipt PDF")?;
 
         Ok(path)
     }
+
+    pub fn render_html(&self) -> anyhow::Result<String> {
+        let html = ReceiptHtml {
+            me: &self.me,
+            images: self
+                .me
+                .branding
+                .html_images()
+                .context("embedding branding images")?,
+            receipt: &self.receipt,
+            invoice: &self.invoice,
+            project: &self.project,
+            client: &self.client,
+            amount_paid: self.amount_paid,
+            balance: self.balance,
+            totals: self.invoice.totals(),
+        };
+
+        html.render().context("rendering receipt HTML template")
+    }
+
+    pub fn save_html(&self, config: &Config) -> anyhow::Result<PathBuf> {
+        let html_dir = get_html_dir(config).context("getting HTML directory")?;
+        let path = html_dir.join(self.html_filename());
+
+        let html = self.render_html()?;
+        std::fs::write(&path, html).context("writing receipt HTML file")?;
+
+        Ok(path)
+    }
+
+    fn write_beancount_to<W: Write>(
+        &self,
+        config: &BeancountConfig,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        let placeholders = Placeholders {
+            client: self.client.name.to_string(),
+            project: self.project.name.to_string(),
+            year: self.receipt.date.year(),
+            number: self.invoice.number,
+        };
+
+        let date = self.receipt.date.to_beancount();
+        let narration = placeholders.fill(&config.receipt_narration);
+        let payment_account_name = self.me.payment_account(&self.receipt.payment_method);
+        let payment_account = placeholders
+            .fill_account(&payment_account_name)
+            .context("getting payment method account")?;
+        // The receivable was booked in the year of the invoice
+        let invoice_placeholders = Placeholders {
+            year: self.invoice.date.year(),
+            ..placeholders.clone()
+        };
+        let receivable_account = invoice_placeholders
+            .fill_account(&config.receivable_account)
+            .context("getting receivable account")?;
+        let amount = |money: Money| {
+            Amount::builder()
+                .num(money.as_decimal())
+                .currency(self.invoice.currency.to_string().into())
+                .build()
+        };
+        let payment_posting = Posting::builder()
+            .account(payment_account)
+            .units(amount(self.amount_paid).into())
+            .build();
+        let receivable_posting = Posting::builder()
+            .account(receivable_account)
+            .units(amount(-self.amount_paid).into())
+            .build();
+        let postings = [payment_posting, receivable_posting].to_vec();
+
+        let txn = ConfiguredTransaction::new(config, &placeholders, date, narration, postings);
+        txn.write_to(writer)?;
+
+        Ok(())
+    }
+
+    pub fn write_beancount_to_string(&self, config: &BeancountConfig) -> anyhow::Result<String> {
+        let mut buf = Vec::<u8>::new();
+        self.write_beancount_to(config, &mut buf)?;
+        let string = String::from_utf8(buf)?;
+
+        Ok(string)
+    }
+
+    /// Save the payment transaction and update the index,
+    /// unless beancount output is disabled.
+    pub fn save_beancount(&self, config: &Config) -> anyhow::Result<Option<PathBuf>> {
+        if !config.beancount.enabled {
+            return Ok(None);
+        }
+
+        let out_path = self.write_beancount(config)?;
+        rebuild_index(config).context("rebuilding beancount index")?;
+
+        Ok(Some(out_path))
+    }
+
+    /// Save the payment transaction without updating the index.
+    pub fn write_beancount(&self, config: &Config) -> anyhow::Result<PathBuf> {
+        let beancount_dir = get_beancount_dir(config).context("getting beancount directory")?;
+        let filename = format!("Receipt_{}.beancount", self.receipt.number());
+        let out_path = beancount_dir.join(&filename);
+        let mut out_file = File::create(&out_path)?;
+
+        self.write_beancount_to(&config.beancount, &mut out_file)?;
+
+        Ok(out_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        config::BeancountConfig, date::DateString, money::Money,
+        test_util::create_full_test_invoice,
+    };
+
+    use super::{amount_paid, amounts_paid, FullReceipt, Receipt, ReceiptNumber};
+
+    #[test]
+    fn test_parse_receipt_number() -> anyhow::Result<()> {
+        let first: ReceiptNumber = "17".parse()?;
+        let second: ReceiptNumber = "17-2".parse()?;
+
+        assert_eq!((first.invoice_num, first.payment_num), (17, 1));
+        assert_eq!((second.invoice_num, second.payment_num), (17, 2));
+        assert_eq!(first.to_string(), "17");
+        assert_eq!(second.to_string(), "17-2");
+        assert!("17-0".parse::<ReceiptNumber>().is_err());
+        assert!("seventeen".parse::<ReceiptNumber>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_amount_paid() -> anyhow::Result<()> {
+        let receipt = |payment_num, amount: Option<&str>| Receipt {
+            invoice_num: 17,
+            payment_num,
+            date: DateString::try_new("2023-01-07".to_owned()).unwrap(),
+            payment_method: "PayPal".to_owned(),
+            amount: amount.map(|amount| Money::from_str(amount).unwrap()),
+        };
+        let total = Money::from_str("100")?;
+
+        let partial = [receipt(1, Some("30")), receipt(2, Some("20"))];
+        assert_eq!(amount_paid(&partial, total), Money::from_str("50")?);
+
+        // Receipts from before partial payments pay off the remaining balance.
+        let legacy = [receipt(1, Some("30")), receipt(2, None)];
+        assert_eq!(amount_paid(&legacy, total), total);
+        assert_eq!(
+            amounts_paid(&legacy, total),
+            [Money::from_str("30")?, Money::from_str("70")?]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_beancount() -> anyhow::Result<()> {
+        let full_invoice = create_full_test_invoice();
+        let full_receipt = FullReceipt {
+            me: full_invoice.me,
+            receipt: Receipt {
+                invoice_num: 17,
+                payment_num: 2,
+                date: DateString::try_new("2023-01-30".to_owned()).unwrap(),
+                payment_method: "PayPal".to_owned(),
+                amount: Some(Money::from_str("9.50")?),
+            },
+            invoice: full_invoice.invoice,
+            project: full_invoice.project,
+            client: full_invoice.client,
+            amount_paid: Money::from_str("9.50")?,
+            balance: Money::ZERO,
+        };
+
+        let beancount_string =
+            full_receipt.write_beancount_to_string(&BeancountConfig::default())?;
+
+        let expected = r#"2023-01-30 * "Payment for Invoice #17 - Test Project #1"
+	Assets:PayPal	9.50 USD
+	Assets:AccountsReceivable	-9.50 USD
+"#;
+
+        assert!(beancount_string.contains(expected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_beancount_in_later_year() -> anyhow::Result<()> {
+        let full_invoice = create_full_test_invoice();
+        let full_receipt = FullReceipt {
+            me: full_invoice.me,
+            receipt: Receipt {
+                invoice_num: 17,
+                payment_num: 1,
+                date: DateString::try_new("2024-01-05".to_owned()).unwrap(),
+                payment_method: "PayPal".to_owned(),
+                amount: Some(Money::from_str("29.50")?),
+            },
+            invoice: full_invoice.invoice,
+            project: full_invoice.project,
+            client: full_invoice.client,
+            amount_paid: Money::from_str("29.50")?,
+            balance: Money::ZERO,
+        };
+        let config = BeancountConfig {
+            receivable_account: "Assets:AccountsReceivable:{year}".to_owned(),
+            ..BeancountConfig::default()
+        };
+
+        let beancount_string = full_receipt.write_beancount_to_string(&config)?;
+
+        assert!(beancount_string.contains("\tAssets:AccountsReceivable:2023\t-29.50 USD\n"));
+
+        Ok(())
+    }
 }
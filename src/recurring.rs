@@ -0,0 +1,431 @@
+use std::{fmt::Display, fs::File, path::Path, str::FromStr};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::{Date, Duration, Month};
+
+use crate::{
+    config::Config,
+    date::DateString,
+    discount::Discount,
+    id::Id,
+    invoice::{Invoice, LineItem},
+    money::{Currency, Percentage},
+    storage::{find_project, get_recurring_dir},
+};
+
+#[derive(Clone, Debug, Error)]
+pub enum CadenceError {
+    #[error("Cadence {0:?} must be monthly, quarterly or \"every N days\"")]
+    Invalid(String),
+}
+
+/// How often a recurring invoice is issued.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub enum Cadence {
+    Monthly,
+    Quarterly,
+    EveryNDays(u16),
+}
+
+impl Display for Cadence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Monthly => f.write_str("monthly"),
+            Self::Quarterly => f.write_str("quarterly"),
+            Self::EveryNDays(days) => write!(f, "every {} days", days),
+        }
+    }
+}
+
+impl FromStr for Cadence {
+    type Err = CadenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CadenceError::Invalid(s.to_owned());
+
+        match s.trim() {
+            "monthly" => Ok(Self::Monthly),
+            "quarterly" => Ok(Self::Quarterly),
+            other => {
+                let days: u16 = other
+                    .strip_prefix("every ")
+                    .and_then(|rest| rest.strip_suffix(" days"))
+                    .ok_or_else(invalid)?
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid())?;
+
+                if days == 0 {
+                    return Err(invalid());
+                }
+
+                Ok(Self::EveryNDays(days))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Cadence {
+    type Error = CadenceError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Cadence> for String {
+    fn from(cadence: Cadence) -> Self {
+        cadence.to_string()
+    }
+}
+
+/// An invoice issued by a recurring schedule.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratedInvoice {
+    pub date: DateString,
+    pub invoice_num: u32,
+}
+
+/// A schedule for issuing the same invoice repeatedly,
+/// stored as `recurring/<name>.yaml`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecurringInvoice {
+    pub project_ref: Id,
+    pub cadence: Cadence,
+    /// Date of the first invoice.
+    pub start_date: DateString,
+    /// Day of the month for monthly and quarterly invoices, defaulting to
+    /// the day of `start_date`. Shorter months use their last day instead.
+    #[serde(default)]
+    pub day_of_month: Option<u8>,
+    /// No invoices are issued after this date.
+    #[serde(default)]
+    pub end_date: Option<DateString>,
+    /// Defaults to the project's or client's payment terms.
+    #[serde(default)]
+    pub days_to_pay: Option<u16>,
+    /// Defaults to the project's or client's currency.
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    /// Defaults to the client's or configured tax rate.
+    #[serde(default)]
+    pub tax_rate: Option<Percentage>,
+    pub items: Vec<LineItem>,
+    #[serde(default)]
+    pub discounts: Vec<Discount>,
+    #[serde(default)]
+    pub conditions: Option<String>,
+    /// Invoices generated so far, so that reruns don't issue them again.
+    #[serde(default)]
+    pub generated: Vec<GeneratedInvoice>,
+}
+
+/// The given day of a month, or the month's last day if it is shorter.
+fn clamped_date(year: i32, month: Month, day: u8) -> Date {
+    let day = day.clamp(1, time::util::days_in_year_month(year, month));
+
+    Date::from_calendar_date(year, month, day).expect("day should be clamped to the month's length")
+}
+
+impl RecurringInvoice {
+    pub fn list(config: &Config) -> anyhow::Result<Vec<String>> {
+        let recurring_dir = get_recurring_dir(config).context("getting recurring directory")?;
+
+        let names: Vec<String> = recurring_dir
+            .read_dir()
+            .context("listing recurring invoice files")?
+            .filter_map(|entry_res| {
+                let entry = entry_res.ok()?;
+                let path = entry.path();
+                if path.extension()? != "yaml" {
+                    return None;
+                }
+                let stem = path.file_stem()?.to_string_lossy();
+
+                Some(stem.into_owned())
+            })
+            .collect();
+
+        Ok(names)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref()).context("reading recurring invoice file")?;
+        let recurring: RecurringInvoice =
+            serde_yaml::from_reader(file).context("parsing recurring invoice yaml")?;
+
+        Ok(recurring)
+    }
+
+    pub fn load(name: &str, config: &Config) -> anyhow::Result<Self> {
+        let recurring_dir = get_recurring_dir(config).context("getting recurring directory")?;
+        let path = recurring_dir.join(format!("{}.yaml", name));
+
+        Self::load_from_path(path).context("loading recurring invoice from file")
+    }
+
+    pub fn save(&self, name: &str, config: &Config) -> anyhow::Result<()> {
+        let recurring_dir = get_recurring_dir(config).context("getting recurring directory")?;
+        let path = recurring_dir.join(format!("{}.yaml", name));
+        let file = File::create(path).context("opening recurring invoice output file")?;
+
+        serde_yaml::to_writer(file, self).context("serializing recurring invoice yaml")?;
+
+        Ok(())
+    }
+
+    /// All invoice dates of the schedule up to and including `as_of`.
+    pub fn occurrences(&self, as_of: Date) -> anyhow::Result<Vec<Date>> {
+        let start: Date = self
+            .start_date
+            .clone()
+            .try_into()
+            .context("parsing start date")?;
+        let end = match &self.end_date {
+            Some(end_date) => {
+                let end: Date = end_date.clone().try_into().context("parsing end date")?;
+                end.min(as_of)
+            }
+            None => as_of,
+        };
+
+        let mut dates = Vec::new();
+
+        match self.cadence {
+            Cadence::EveryNDays(days) => {
+                let mut date = start;
+                while date <= end {
+                    dates.push(date);
+                    date += Duration::days(days.into());
+                }
+            }
+            Cadence::Monthly | Cadence::Quarterly => {
+                let step = if self.cadence == Cadence::Monthly {
+                    1
+                } else {
+                    3
+                };
+                let day = self.day_of_month.unwrap_or(start.day());
+
+                // Count months from year 0 so that stepping wraps around years.
+                let mut month_index = start.year() * 12 + i32::from(u8::from(start.month())) - 1;
+                loop {
+                    let year = month_index.div_euclid(12);
+                    let month = Month::try_from(month_index.rem_euclid(12) as u8 + 1)
+                        .expect("month index should be in range");
+                    let date = clamped_date(year, month, day);
+
+                    if date > end {
+                        break;
+                    }
+                    if date >= start {
+                        dates.push(date);
+                    }
+
+                    month_index += step;
+                }
+            }
+        }
+
+        Ok(dates)
+    }
+
+    /// Invoice dates up to and including `as_of` which haven't been generated yet.
+    pub fn due_dates(&self, as_of: Date) -> anyhow::Result<Vec<Date>> {
+        let generated: Vec<DateString> = self
+            .generated
+            .iter()
+            .map(|generated| generated.date.clone())
+            .collect();
+
+        let due = self
+            .occurrences(as_of)?
+            .into_iter()
+            .filter(|date| !generated.contains(&DateString::from(*date)))
+            .collect();
+
+        Ok(due)
+    }
+
+    /// Create the invoice for one date of the schedule.
+    pub fn to_invoice(&self, number: u32, date: Date, config: &Config) -> anyhow::Result<Invoice> {
+        let project = find_project(&self.project_ref, config)
+            .with_context(|| format!("finding project {:?}", self.project_ref))?;
+
+        let currency = match &self.currency {
+            Some(currency) => currency.clone(),
+            None => project
+                .default_currency(config)
+                .context("getting default currency")?,
+        };
+        let tax_rate = match self.tax_rate {
+            Some(tax_rate) => Some(tax_rate),
+            None => project
+                .default_tax_rate(config)
+                .context("getting default tax rate")?,
+        };
+
+        let days_to_pay = match self.days_to_pay {
+            Some(days_to_pay) => days_to_pay,
+            None => project
+                .default_days_to_pay(config)
+                .context("getting default days to pay")?,
+        };
+        let due_date = date + Duration::days(days_to_pay.into());
+
+        let invoice = Invoice {
+            number,
+            project_ref: self.project_ref.clone(),
+            date: date.into(),
+            due_date: due_date.into(),
+            currency,
+            tax_rate,
+            items: self.items.clone(),
+            expenses: Vec::new(),
+            discounts: self.discounts.clone(),
+            conditions: self.conditions.clone(),
+        };
+
+        Ok(invoice)
+    }
+
+    /// Create and save every invoice due up to and including `as_of`.
+    ///
+    /// Each date is recorded in the schedule before its invoice is saved,
+    /// so that it is only ever invoiced once, even if a later step fails.
+    pub fn generate_due(
+        &mut self,
+        name: &str,
+        as_of: Date,
+        config: &Config,
+    ) -> anyhow::Result<Vec<Invoice>> {
+        let mut invoices = Vec::new();
+
+        for date in self.due_dates(as_of)? {
+            let number = Invoice::get_next_number(config).context("getting next invoice number")?;
+            let invoice = self
+                .to_invoice(number, date, config)
+                .with_context(|| format!("creating invoice for {}", date))?;
+            invoice.validate(config).context("validating invoice")?;
+
+            self.generated.push(GeneratedInvoice {
+                date: date.into(),
+                invoice_num: number,
+            });
+            self.save(name, config)
+                .context("recording generated invoice")?;
+
+            if let Err(err) = invoice.save(config) {
+                // Forget the date again so that the next run retries it
+                self.generated.pop();
+                self.save(name, config)
+                    .context("removing record of unsaved invoice")?;
+                return Err(err).context("saving invoice yaml");
+            }
+
+            invoices.push(invoice);
+        }
+
+        Ok(invoices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use time::macros::date;
+
+    use crate::date::DateString;
+
+    use super::{Cadence, GeneratedInvoice, RecurringInvoice};
+
+    fn create_test_recurring(cadence: Cadence, start_date: &str) -> RecurringInvoice {
+        RecurringInvoice {
+            project_ref: "Test Project #1".to_owned().into(),
+            cadence,
+            start_date: DateString::try_new(start_date.to_owned()).unwrap(),
+            day_of_month: None,
+            end_date: None,
+            days_to_pay: Some(14),
+            currency: None,
+            tax_rate: None,
+            items: Vec::new(),
+            discounts: Vec::new(),
+            conditions: None,
+            generated: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_cadence() -> anyhow::Result<()> {
+        assert_eq!(Cadence::from_str("monthly")?, Cadence::Monthly);
+        assert_eq!(Cadence::from_str("quarterly")?, Cadence::Quarterly);
+        assert_eq!(Cadence::from_str("every 14 days")?, Cadence::EveryNDays(14));
+        assert_eq!(Cadence::EveryNDays(14).to_string(), "every 14 days");
+        assert!(Cadence::from_str("every 0 days").is_err());
+        assert!(Cadence::from_str("weekly").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_monthly_occurrences() -> anyhow::Result<()> {
+        let mut recurring = create_test_recurring(Cadence::Monthly, "2023-01-15");
+        recurring.day_of_month = Some(31);
+
+        let dates = recurring.occurrences(date!(2023 - 04 - 30))?;
+
+        assert_eq!(
+            dates,
+            [
+                date!(2023 - 01 - 31),
+                date!(2023 - 02 - 28),
+                date!(2023 - 03 - 31),
+                date!(2023 - 04 - 30),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quarterly_occurrences() -> anyhow::Result<()> {
+        let mut recurring = create_test_recurring(Cadence::Quarterly, "2022-11-01");
+        recurring.end_date = Some(DateString::try_new("2023-06-30".to_owned()).unwrap());
+
+        let dates = recurring.occurrences(date!(2024 - 01 - 01))?;
+
+        assert_eq!(
+            dates,
+            [
+                date!(2022 - 11 - 01),
+                date!(2023 - 02 - 01),
+                date!(2023 - 05 - 01)
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_due_dates_skip_generated() -> anyhow::Result<()> {
+        let mut recurring = create_test_recurring(Cadence::EveryNDays(14), "2023-01-02");
+        recurring.generated.push(GeneratedInvoice {
+            date: DateString::try_new("2023-01-02".to_owned()).unwrap(),
+            invoice_num: 17,
+        });
+
+        let dates = recurring.due_dates(date!(2023 - 01 - 31))?;
+
+        assert_eq!(dates, [date!(2023 - 01 - 16), date!(2023 - 01 - 30)]);
+
+        Ok(())
+    }
+}
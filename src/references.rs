@@ -0,0 +1,259 @@
+use std::{fmt::Display, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    beancount::rebuild_index,
+    config::Config,
+    credit_note::CreditNote,
+    expense::Expense,
+    id::Id,
+    invoice::Invoice,
+    quote::Quote,
+    receipt::Receipt,
+    recurring::RecurringInvoice,
+    storage::{
+        get_beancount_dir, get_credit_notes_dir, get_expenses_dir, get_invoices_dir,
+        get_quotes_dir, get_receipts_dir, get_recurring_dir,
+    },
+    timelog::TimeLog,
+};
+
+/// Everything saved which refers to a project by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProjectReferences {
+    pub invoices: Vec<u32>,
+    pub quotes: Vec<u32>,
+    pub expenses: Vec<u32>,
+    pub recurring: Vec<String>,
+    pub time_log: bool,
+}
+
+impl ProjectReferences {
+    pub fn find(project: &Id, config: &Config) -> anyhow::Result<Self> {
+        let mut references = Self::default();
+
+        for number in Invoice::list(config).context("listing invoices")? {
+            let invoice = Invoice::load(number, config)
+                .with_context(|| format!("loading invoice {}", number))?;
+            if invoice.project_ref == *project {
+                references.invoices.push(number);
+            }
+        }
+
+        for number in Quote::list(config).context("listing quotes")? {
+            let quote =
+                Quote::load(number, config).with_context(|| format!("loading quote {}", number))?;
+            if quote.project_ref == *project {
+                references.quotes.push(number);
+            }
+        }
+
+        for number in Expense::list(config).context("listing expenses")? {
+            let expense = Expense::load(number, config)
+                .with_context(|| format!("loading expense {}", number))?;
+            if expense.project_ref == *project {
+                references.expenses.push(number);
+            }
+        }
+
+        for name in RecurringInvoice::list(config).context("listing recurring invoices")? {
+            let recurring = RecurringInvoice::load(&name, config)
+                .with_context(|| format!("loading recurring invoice {:?}", name))?;
+            if recurring.project_ref == *project {
+                references.recurring.push(name);
+            }
+        }
+
+        references.time_log = TimeLog::list(config)
+            .context("listing time logs")?
+            .contains(project);
+
+        references.invoices.sort();
+        references.quotes.sort();
+        references.expenses.sort();
+        references.recurring.sort();
+
+        Ok(references)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.invoices.is_empty()
+            && self.quotes.is_empty()
+            && self.expenses.is_empty()
+            && self.recurring.is_empty()
+            && !self.time_log
+    }
+
+    /// A summary such as `invoices 3, 5, quote 2 and a time log`.
+    pub fn describe(&self) -> String {
+        fn numbered<T: Display>(singular: &str, plural: &str, items: &[T]) -> Option<String> {
+            let noun = match items.len() {
+                0 => return None,
+                1 => singular,
+                _ => plural,
+            };
+            let numbers: Vec<String> = items.iter().map(ToString::to_string).collect();
+
+            Some(format!("{} {}", noun, numbers.join(", ")))
+        }
+
+        let mut parts: Vec<String> = [
+            numbered("invoice", "invoices", &self.invoices),
+            numbered("quote", "quotes", &self.quotes),
+            numbered("expense", "expenses", &self.expenses),
+            numbered("recurring invoice", "recurring invoices", &self.recurring),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if self.time_log {
+            parts.push("a time log".to_owned());
+        }
+
+        match parts.split_last() {
+            None => String::new(),
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        }
+    }
+
+    /// Point every reference at the project's new name.
+    pub fn rename(&self, old: &Id, new: &Id, config: &Config) -> anyhow::Result<()> {
+        for &number in &self.invoices {
+            let mut invoice = Invoice::load(number, config)
+                .with_context(|| format!("loading invoice {}", number))?;
+            invoice.project_ref = new.clone();
+            invoice
+                .save(config)
+                .with_context(|| format!("saving invoice {}", number))?;
+        }
+
+        for &number in &self.quotes {
+            let mut quote =
+                Quote::load(number, config).with_context(|| format!("loading quote {}", number))?;
+            quote.project_ref = new.clone();
+            quote
+                .save(config)
+                .with_context(|| format!("saving quote {}", number))?;
+        }
+
+        for &number in &self.expenses {
+            let mut expense = Expense::load(number, config)
+                .with_context(|| format!("loading expense {}", number))?;
+            expense.project_ref = new.clone();
+            expense
+                .save(config)
+                .with_context(|| format!("saving expense {}", number))?;
+        }
+
+        for name in &self.recurring {
+            let mut recurring = RecurringInvoice::load(name, config)
+                .with_context(|| format!("loading recurring invoice {:?}", name))?;
+            recurring.project_ref = new.clone();
+            recurring
+                .save(name, config)
+                .with_context(|| format!("saving recurring invoice {:?}", name))?;
+        }
+
+        if self.time_log {
+            TimeLog::rename(old, new, config).context("renaming time log")?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete every reference, including the receipts and credit notes
+    /// of the deleted invoices and their beancount files.
+    /// Rendered PDFs are kept.
+    pub fn delete(&self, project: &Id, config: &Config) -> anyhow::Result<()> {
+        let beancount_dir = get_beancount_dir(config).context("getting beancount directory")?;
+
+        let receipts_dir = get_receipts_dir(config).context("getting receipts directory")?;
+        let credit_notes_dir =
+            get_credit_notes_dir(config).context("getting credit notes directory")?;
+        let invoices_dir = get_invoices_dir(config).context("getting invoices directory")?;
+        for &number in &self.invoices {
+            for receipt in Receipt::list_for_invoice(number, config)
+                .with_context(|| format!("listing receipts for invoice {}", number))?
+            {
+                remove_if_exists(receipts_dir.join(receipt.filename()))?;
+                remove_if_exists(
+                    beancount_dir.join(format!("Receipt_{}.beancount", receipt.number())),
+                )?;
+            }
+
+            for credit_note in CreditNote::list_for_invoice(number, config)
+                .with_context(|| format!("listing credit notes for invoice {}", number))?
+            {
+                remove_if_exists(credit_notes_dir.join(credit_note.filename()))?;
+                remove_if_exists(
+                    beancount_dir.join(format!("CreditNote_{}.beancount", credit_note.number)),
+                )?;
+            }
+
+            remove_if_exists(invoices_dir.join(format!("{}.yaml", number)))?;
+            remove_if_exists(beancount_dir.join(format!("Invoice_{}.beancount", number)))?;
+        }
+
+        let quotes_dir = get_quotes_dir(config).context("getting quotes directory")?;
+        for &number in &self.quotes {
+            remove_if_exists(quotes_dir.join(format!("{}.yaml", number)))?;
+        }
+
+        let expenses_dir = get_expenses_dir(config).context("getting expenses directory")?;
+        for &number in &self.expenses {
+            remove_if_exists(expenses_dir.join(format!("{}.yaml", number)))?;
+            remove_if_exists(beancount_dir.join(format!("Expense_{}.beancount", number)))?;
+        }
+
+        let recurring_dir = get_recurring_dir(config).context("getting recurring directory")?;
+        for name in &self.recurring {
+            remove_if_exists(recurring_dir.join(format!("{}.yaml", name)))?;
+        }
+
+        if self.time_log {
+            TimeLog::delete(project, config).context("deleting time log")?;
+        }
+
+        if config.beancount.enabled {
+            rebuild_index(config).context("rebuilding beancount index")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn remove_if_exists(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+
+    if path.exists() {
+        std::fs::remove_file(path).with_context(|| format!("removing {:?}", path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProjectReferences;
+
+    #[test]
+    fn test_describe() {
+        let mut references = ProjectReferences::default();
+        assert!(references.is_empty());
+        assert_eq!(references.describe(), "");
+
+        references.invoices = vec![3];
+        assert_eq!(references.describe(), "invoice 3");
+
+        references.invoices.push(5);
+        references.quotes = vec![2];
+        references.time_log = true;
+        assert!(!references.is_empty());
+        assert_eq!(
+            references.describe(),
+            "invoices 3, 5, quote 2 and a time log"
+        );
+    }
+}
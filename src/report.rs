@@ -0,0 +1,600 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use anyhow::Context;
+use serde::Serialize;
+use time::Date;
+
+use crate::{
+    config::Config,
+    credit_note::{amounts_credited, CreditNote},
+    id::Id,
+    invoice::Invoice,
+    money::{serialize_amount, Currency, Money},
+    receipt::{amounts_paid, Receipt},
+    storage::find_project,
+};
+
+/// How a report is printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for reading in a terminal
+    #[default]
+    Table,
+    Csv,
+    Json,
+}
+
+/// A row of a report, which can be printed in every `OutputFormat`.
+pub trait ReportRow: Serialize {
+    const HEADERS: &'static [&'static str];
+    /// The number of leading columns which are text rather than amounts.
+    /// Text columns are aligned left in tables, amounts right.
+    const TEXT_COLUMNS: usize;
+
+    fn cells(&self) -> Vec<String>;
+}
+
+fn render_table<R: ReportRow>(rows: &[R]) -> String {
+    let cells: Vec<Vec<String>> = rows.iter().map(ReportRow::cells).collect();
+
+    let mut widths: Vec<usize> = R::HEADERS.iter().map(|header| header.len()).collect();
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    let headers: Vec<String> = R::HEADERS.iter().map(|header| header.to_string()).collect();
+    for row in std::iter::once(headers).chain(cells) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| {
+                if i < R::TEXT_COLUMNS {
+                    format!("{:<width$}", cell)
+                } else {
+                    format!("{:>width$}", cell)
+                }
+            })
+            .collect();
+        _ = writeln!(table, "{}", line.join("  ").trim_end());
+    }
+
+    table
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn render_csv<R: ReportRow>(rows: &[R]) -> String {
+    let headers: Vec<String> = R::HEADERS.iter().map(|header| header.to_string()).collect();
+
+    let mut csv = String::new();
+    for row in std::iter::once(headers).chain(rows.iter().map(ReportRow::cells)) {
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        _ = writeln!(csv, "{}", fields.join(","));
+    }
+
+    csv
+}
+
+/// Render report rows in the given format.
+pub fn render_report<R: ReportRow>(rows: &[R], format: OutputFormat) -> anyhow::Result<String> {
+    let rendered = match format {
+        OutputFormat::Table => render_table(rows),
+        OutputFormat::Csv => render_csv(rows),
+        OutputFormat::Json => {
+            let mut json = serde_json::to_string_pretty(rows).context("serializing report")?;
+            json.push('\n');
+            json
+        }
+    };
+
+    Ok(rendered)
+}
+
+/// Find the client of a project, remembering it for the project's other invoices.
+fn client_of(
+    project: &Id,
+    clients: &mut BTreeMap<String, Id>,
+    config: &Config,
+) -> anyhow::Result<Id> {
+    if let Some(client) = clients.get(&project.to_string()) {
+        return Ok(client.clone());
+    }
+
+    let found =
+        find_project(project, config).with_context(|| format!("finding project {:?}", project))?;
+    clients.insert(project.to_string(), found.client_ref.clone());
+
+    Ok(found.client_ref)
+}
+
+/// Time since an invoice's due date, in the usual accounts receivable buckets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgingBucket {
+    /// Not due yet, or due today.
+    Current,
+    Days1To30,
+    Days31To60,
+    Days61To90,
+    Over90,
+}
+
+impl AgingBucket {
+    pub fn for_days_overdue(days: i64) -> Self {
+        match days {
+            i64::MIN..=0 => Self::Current,
+            1..=30 => Self::Days1To30,
+            31..=60 => Self::Days31To60,
+            61..=90 => Self::Days61To90,
+            _ => Self::Over90,
+        }
+    }
+}
+
+/// An invoice which is not fully paid yet.
+#[derive(Clone, Debug)]
+pub struct OutstandingInvoice {
+    pub client: Id,
+    pub invoice: Invoice,
+    /// The amount still owed after all credit notes and payments.
+    pub balance: Money,
+}
+
+impl OutstandingInvoice {
+    /// Load every invoice with an outstanding balance on `as_of`,
+    /// ignoring invoices, credit notes and receipts dated after it.
+    pub fn load_all(as_of: Date, config: &Config) -> anyhow::Result<Vec<Self>> {
+        let mut numbers = Invoice::list(config).context("listing invoices")?;
+        numbers.sort();
+
+        let mut clients = BTreeMap::<String, Id>::new();
+        let mut outstanding = Vec::new();
+        for number in numbers {
+            let invoice = Invoice::load(number, config)
+                .with_context(|| format!("loading invoice {}", number))?;
+            let date: Date = invoice
+                .date
+                .clone()
+                .try_into()
+                .with_context(|| format!("parsing date of invoice {}", number))?;
+            if date > as_of {
+                continue;
+            }
+
+            let balance = invoice
+                .outstanding_balance_as_of(as_of, config)
+                .with_context(|| format!("getting balance of invoice {}", number))?;
+            if balance <= Money::ZERO {
+                continue;
+            }
+
+            let client = client_of(&invoice.project_ref, &mut clients, config)?;
+
+            outstanding.push(Self {
+                client,
+                invoice,
+                balance,
+            });
+        }
+
+        Ok(outstanding)
+    }
+}
+
+/// Outstanding balances of a client in one currency, by time since the due date.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AgingRow {
+    pub client: Id,
+    pub currency: Currency,
+    #[serde(serialize_with = "serialize_amount")]
+    pub current: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub days_1_30: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub days_31_60: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub days_61_90: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub over_90: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub total: Money,
+}
+
+impl AgingRow {
+    fn new(client: Id, currency: Currency) -> Self {
+        Self {
+            client,
+            currency,
+            current: Money::ZERO,
+            days_1_30: Money::ZERO,
+            days_31_60: Money::ZERO,
+            days_61_90: Money::ZERO,
+            over_90: Money::ZERO,
+            total: Money::ZERO,
+        }
+    }
+
+    fn add(&mut self, bucket: AgingBucket, amount: Money) {
+        let column = match bucket {
+            AgingBucket::Current => &mut self.current,
+            AgingBucket::Days1To30 => &mut self.days_1_30,
+            AgingBucket::Days31To60 => &mut self.days_31_60,
+            AgingBucket::Days61To90 => &mut self.days_61_90,
+            AgingBucket::Over90 => &mut self.over_90,
+        };
+        *column = *column + amount;
+        self.total = self.total + amount;
+    }
+}
+
+impl ReportRow for AgingRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Client", "Currency", "Current", "1-30", "31-60", "61-90", "90+", "Total",
+    ];
+    const TEXT_COLUMNS: usize = 2;
+
+    fn cells(&self) -> Vec<String> {
+        [
+            self.client.to_string(),
+            self.currency.to_string(),
+            self.current.to_string(),
+            self.days_1_30.to_string(),
+            self.days_31_60.to_string(),
+            self.days_61_90.to_string(),
+            self.over_90.to_string(),
+            self.total.to_string(),
+        ]
+        .to_vec()
+    }
+}
+
+/// Sum up outstanding balances per client and currency,
+/// bucketed by the number of days between each due date and `as_of`.
+pub fn aging_report(
+    outstanding: &[OutstandingInvoice],
+    as_of: Date,
+) -> anyhow::Result<Vec<AgingRow>> {
+    let mut rows = BTreeMap::<(String, String), AgingRow>::new();
+
+    for item in outstanding {
+        let due_date: Date = item
+            .invoice
+            .due_date
+            .clone()
+            .try_into()
+            .with_context(|| format!("parsing due date of invoice {}", item.invoice.number))?;
+        let bucket = AgingBucket::for_days_overdue((as_of - due_date).whole_days());
+
+        let key = (item.client.to_string(), item.invoice.currency.to_string());
+        rows.entry(key)
+            .or_insert_with(|| AgingRow::new(item.client.clone(), item.invoice.currency.clone()))
+            .add(bucket, item.balance);
+    }
+
+    Ok(rows.into_values().collect())
+}
+
+/// How revenue is grouped into rows.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum GroupBy {
+    Client,
+    Project,
+    Month,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RevenueKind {
+    Invoiced,
+    Credited,
+    Received,
+}
+
+/// An amount invoiced, credited or received on a given date.
+#[derive(Clone, Debug)]
+pub struct RevenueEntry {
+    pub date: Date,
+    pub client: Id,
+    pub project: Id,
+    pub currency: Currency,
+    pub kind: RevenueKind,
+    pub amount: Money,
+}
+
+impl RevenueEntry {
+    /// Load the totals of all invoices, dated by the invoice,
+    /// and the amounts of all credit notes and receipts, dated by themselves.
+    pub fn load_all(config: &Config) -> anyhow::Result<Vec<Self>> {
+        let mut numbers = Invoice::list(config).context("listing invoices")?;
+        numbers.sort();
+
+        let mut clients = BTreeMap::<String, Id>::new();
+        let mut entries = Vec::new();
+        for number in numbers {
+            let invoice = Invoice::load(number, config)
+                .with_context(|| format!("loading invoice {}", number))?;
+            let client = client_of(&invoice.project_ref, &mut clients, config)?;
+            let entry = |date: Date, kind: RevenueKind, amount: Money| Self {
+                date,
+                client: client.clone(),
+                project: invoice.project_ref.clone(),
+                currency: invoice.currency.clone(),
+                kind,
+                amount,
+            };
+
+            let total = invoice.total();
+            let date: Date = invoice
+                .date
+                .clone()
+                .try_into()
+                .with_context(|| format!("parsing date of invoice {}", number))?;
+            entries.push(entry(date, RevenueKind::Invoiced, total));
+
+            let credit_notes =
+                CreditNote::list_for_invoice(number, config).context("loading credit notes")?;
+            let credited = amounts_credited(&credit_notes, total);
+            let amount_due = total - credited.iter().copied().sum::<Money>();
+            for (credit_note, amount) in credit_notes.iter().zip(credited) {
+                let date: Date = credit_note.date.clone().try_into().with_context(|| {
+                    format!("parsing date of credit note {}", credit_note.number)
+                })?;
+                entries.push(entry(date, RevenueKind::Credited, amount));
+            }
+
+            let receipts = Receipt::list_for_invoice(number, config).context("loading receipts")?;
+            let paid = amounts_paid(&receipts, amount_due);
+            for (receipt, amount) in receipts.iter().zip(paid) {
+                let date: Date = receipt
+                    .date
+                    .clone()
+                    .try_into()
+                    .with_context(|| format!("parsing date of receipt {}", receipt.number()))?;
+                entries.push(entry(date, RevenueKind::Received, amount));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn group(&self, group_by: GroupBy) -> String {
+        match group_by {
+            GroupBy::Client => self.client.to_string(),
+            GroupBy::Project => self.project.to_string(),
+            GroupBy::Month => format!("{}-{:02}", self.date.year(), u8::from(self.date.month())),
+        }
+    }
+}
+
+/// Revenue of a client, project or month in one currency.
+///
+/// `net_invoiced` is the revenue on an accrual basis (by invoice and credit note date),
+/// `received` the revenue on a cash basis (by receipt date).
+/// All amounts include tax.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RevenueRow {
+    pub group: String,
+    pub currency: Currency,
+    #[serde(serialize_with = "serialize_amount")]
+    pub invoiced: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub credited: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub net_invoiced: Money,
+    #[serde(serialize_with = "serialize_amount")]
+    pub received: Money,
+}
+
+impl RevenueRow {
+    fn new(group: String, currency: Currency) -> Self {
+        Self {
+            group,
+            currency,
+            invoiced: Money::ZERO,
+            credited: Money::ZERO,
+            net_invoiced: Money::ZERO,
+            received: Money::ZERO,
+        }
+    }
+
+    fn add(&mut self, kind: RevenueKind, amount: Money) {
+        match kind {
+            RevenueKind::Invoiced => {
+                self.invoiced = self.invoiced + amount;
+                self.net_invoiced = self.net_invoiced + amount;
+            }
+            RevenueKind::Credited => {
+                self.credited = self.credited + amount;
+                self.net_invoiced = self.net_invoiced - amount;
+            }
+            RevenueKind::Received => self.received = self.received + amount,
+        }
+    }
+}
+
+impl ReportRow for RevenueRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Group",
+        "Currency",
+        "Invoiced",
+        "Credited",
+        "Net invoiced",
+        "Received",
+    ];
+    const TEXT_COLUMNS: usize = 2;
+
+    fn cells(&self) -> Vec<String> {
+        [
+            self.group.clone(),
+            self.currency.to_string(),
+            self.invoiced.to_string(),
+            self.credited.to_string(),
+            self.net_invoiced.to_string(),
+            self.received.to_string(),
+        ]
+        .to_vec()
+    }
+}
+
+/// Sum up the entries dated from `from` to `to` (both inclusive, and optional)
+/// per group and currency.
+pub fn revenue_report(
+    entries: &[RevenueEntry],
+    from: Option<Date>,
+    to: Option<Date>,
+    group_by: GroupBy,
+) -> Vec<RevenueRow> {
+    let mut rows = BTreeMap::<(String, String), RevenueRow>::new();
+
+    let in_range =
+        |date: Date| from.map_or(true, |from| date >= from) && to.map_or(true, |to| date <= to);
+    for entry in entries.iter().filter(|entry| in_range(entry.date)) {
+        let group = entry.group(group_by);
+        let key = (group.clone(), entry.currency.to_string());
+        rows.entry(key)
+            .or_insert_with(|| RevenueRow::new(group, entry.currency.clone()))
+            .add(entry.kind, entry.amount);
+    }
+
+    rows.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use time::macros::date;
+
+    use crate::{date::DateString, money::Money, test_util::create_full_test_invoice};
+
+    use super::{
+        aging_report, render_report, revenue_report, AgingBucket, AgingRow, GroupBy, OutputFormat,
+        OutstandingInvoice, RevenueEntry, RevenueKind,
+    };
+
+    fn outstanding(client: &str, due_date: &str, balance: &str) -> OutstandingInvoice {
+        let mut invoice = create_full_test_invoice().invoice;
+        invoice.due_date = DateString::try_new(due_date.to_owned()).unwrap();
+
+        OutstandingInvoice {
+            client: client.to_owned().into(),
+            invoice,
+            balance: Money::from_str(balance).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_aging_buckets() {
+        assert_eq!(AgingBucket::for_days_overdue(-3), AgingBucket::Current);
+        assert_eq!(AgingBucket::for_days_overdue(0), AgingBucket::Current);
+        assert_eq!(AgingBucket::for_days_overdue(1), AgingBucket::Days1To30);
+        assert_eq!(AgingBucket::for_days_overdue(30), AgingBucket::Days1To30);
+        assert_eq!(AgingBucket::for_days_overdue(31), AgingBucket::Days31To60);
+        assert_eq!(AgingBucket::for_days_overdue(90), AgingBucket::Days61To90);
+        assert_eq!(AgingBucket::for_days_overdue(91), AgingBucket::Over90);
+    }
+
+    #[test]
+    fn test_aging_report() -> anyhow::Result<()> {
+        let outstanding = [
+            outstanding("Beta", "2023-03-01", "10"),
+            outstanding("Alpha", "2023-03-20", "29.50"),
+            outstanding("Alpha", "2023-02-15", "5"),
+            outstanding("Alpha", "2022-11-01", "100"),
+        ];
+
+        let rows = aging_report(&outstanding, date!(2023 - 03 - 20))?;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].client.to_string(), "Alpha");
+        assert_eq!(rows[0].current, Money::from_str("29.50")?);
+        assert_eq!(rows[0].days_31_60, Money::from_str("5")?);
+        assert_eq!(rows[0].over_90, Money::from_str("100")?);
+        assert_eq!(rows[0].total, Money::from_str("134.50")?);
+        assert_eq!(rows[1].client.to_string(), "Beta");
+        assert_eq!(rows[1].days_1_30, Money::from_str("10")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_report() -> anyhow::Result<()> {
+        let outstanding = [outstanding("Client, Inc.", "2023-03-01", "10")];
+        let rows: Vec<AgingRow> = aging_report(&outstanding, date!(2023 - 03 - 20))?;
+
+        let table = render_report(&rows, OutputFormat::Table)?;
+        assert_eq!(
+            table,
+            "Client        Currency  Current   1-30  31-60  61-90   90+  Total\n\
+             Client, Inc.  USD          0.00  10.00   0.00   0.00  0.00  10.00\n"
+        );
+
+        let csv = render_report(&rows, OutputFormat::Csv)?;
+        assert_eq!(
+            csv,
+            "Client,Currency,Current,1-30,31-60,61-90,90+,Total\n\
+             \"Client, Inc.\",USD,0.00,10.00,0.00,0.00,0.00,10.00\n"
+        );
+
+        let json = render_report(&rows, OutputFormat::Json)?;
+        assert!(json.contains("\"client\": \"Client, Inc.\""));
+        assert!(json.contains("\"days_1_30\": \"10.00\""));
+
+        Ok(())
+    }
+
+    fn revenue(date: &str, client: &str, kind: RevenueKind, amount: &str) -> RevenueEntry {
+        RevenueEntry {
+            date: DateString::try_new(date.to_owned())
+                .unwrap()
+                .try_into()
+                .unwrap(),
+            client: client.to_owned().into(),
+            project: "Test Project #1".to_owned().into(),
+            currency: Default::default(),
+            kind,
+            amount: Money::from_str(amount).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_revenue_report() -> anyhow::Result<()> {
+        let entries = [
+            revenue("2022-12-20", "Alpha", RevenueKind::Invoiced, "50"),
+            revenue("2023-01-07", "Alpha", RevenueKind::Invoiced, "100"),
+            revenue("2023-01-10", "Beta", RevenueKind::Invoiced, "30"),
+            revenue("2023-01-15", "Alpha", RevenueKind::Received, "50"),
+            revenue("2023-02-01", "Alpha", RevenueKind::Credited, "20"),
+            revenue("2023-02-03", "Alpha", RevenueKind::Received, "80"),
+            revenue("2023-04-01", "Beta", RevenueKind::Received, "30"),
+        ];
+        let from = Some(date!(2023 - 01 - 01));
+        let to = Some(date!(2023 - 03 - 31));
+
+        let by_client = revenue_report(&entries, from, to, GroupBy::Client);
+        assert_eq!(by_client.len(), 2);
+        assert_eq!(by_client[0].group, "Alpha");
+        assert_eq!(by_client[0].invoiced, Money::from_str("100")?);
+        assert_eq!(by_client[0].credited, Money::from_str("20")?);
+        assert_eq!(by_client[0].net_invoiced, Money::from_str("80")?);
+        assert_eq!(by_client[0].received, Money::from_str("130")?);
+        assert_eq!(by_client[1].group, "Beta");
+        assert_eq!(by_client[1].received, Money::ZERO);
+
+        let by_month = revenue_report(&entries, None, None, GroupBy::Month);
+        let months: Vec<&str> = by_month.iter().map(|row| row.group.as_str()).collect();
+        assert_eq!(months, ["2022-12", "2023-01", "2023-02", "2023-04"]);
+        assert_eq!(by_month[1].net_invoiced, Money::from_str("130")?);
+        assert_eq!(by_month[2].net_invoiced, Money::from_str("-20")?);
+
+        Ok(())
+    }
+}
@@ -27,7 +27,7 @@ fn get_default_data_dir() -> anyhow::Result<PathBuf> {
     return Ok(data_dir.to_owned());
 }
 
-fn expand_tilde(path: &PathBuf) -> PathBuf {
+pub fn expand_tilde(path: &PathBuf) -> PathBuf {
     let path_str = path.as_os_str().to_string_lossy();
     let expanded_str = shellexpand::tilde(&path_str).to_string();
     let expanded_path = expanded_str.into();
@@ -77,6 +77,41 @@ pub fn get_receipts_dir(config: &Config) -> anyhow::Result<PathBuf> {
     Ok(receipts_dir)
 }
 
+pub fn get_timelogs_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let timelogs_dir = data_dir.join("timelogs");
+    std::fs::create_dir_all(&timelogs_dir).context("creating timelogs directory")?;
+    Ok(timelogs_dir)
+}
+
+pub fn get_expenses_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let expenses_dir = data_dir.join("expenses");
+    std::fs::create_dir_all(&expenses_dir).context("creating expenses directory")?;
+    Ok(expenses_dir)
+}
+
+pub fn get_recurring_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let recurring_dir = data_dir.join("recurring");
+    std::fs::create_dir_all(&recurring_dir).context("creating recurring directory")?;
+    Ok(recurring_dir)
+}
+
+pub fn get_quotes_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let quotes_dir = data_dir.join("quotes");
+    std::fs::create_dir_all(&quotes_dir).context("creating quotes directory")?;
+    Ok(quotes_dir)
+}
+
+pub fn get_credit_notes_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let credit_notes_dir = data_dir.join("credit_notes");
+    std::fs::create_dir_all(&credit_notes_dir).context("creating credit notes directory")?;
+    Ok(credit_notes_dir)
+}
+
 pub fn get_pdfs_dir(config: &Config) -> anyhow::Result<PathBuf> {
     let data_dir = get_data_dir(config).context("getting data directory")?;
     let pdfs_dir = data_dir.join("pdfs");
@@ -84,6 +119,28 @@ pub fn get_pdfs_dir(config: &Config) -> anyhow::Result<PathBuf> {
     Ok(pdfs_dir)
 }
 
+/// The directory containing the user's template sets.
+pub fn get_templates_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    if let Some(dir) = config.templates.dir.as_ref().map(expand_tilde) {
+        if !dir.is_absolute() {
+            bail!("templates dir must be absolute (found {:?})", dir);
+        }
+        return Ok(dir);
+    }
+
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let templates_dir = data_dir.join("templates");
+    std::fs::create_dir_all(&templates_dir).context("creating templates directory")?;
+    Ok(templates_dir)
+}
+
+pub fn get_html_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let data_dir = get_data_dir(config).context("getting data directory")?;
+    let html_dir = data_dir.join("html");
+    std::fs::create_dir_all(&html_dir).context("creating html directory")?;
+    Ok(html_dir)
+}
+
 pub fn get_beancount_dir(config: &Config) -> anyhow::Result<PathBuf> {
     let data_dir = get_data_dir(config).context("getting data directory")?;
     let beancount_dir = data_dir.join("beancount");
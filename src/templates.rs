@@ -0,0 +1,354 @@
+//! User-supplied LaTeX templates, loaded at runtime.
+//!
+//! A template set is a subdirectory of the templates directory. Its `*.tex`
+//! files are [tera](https://keats.github.io/tera/) templates (`invoice.tex`,
+//! `receipt.tex`, `quote.tex` and `credit_note.tex`, plus any partials they
+//! include), and all other files, such as document classes, fonts and logos,
+//! are added to the LaTeX build. Documents without a template in the set
+//! fall back to the built-in ones.
+//!
+//! With the Typst renderer, the set's `*.typ` files replace the built-in Typst
+//! templates of the same name, and receive the same data as `data.json`.
+
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use anyhow::{bail, Context};
+use askama_escape::Escaper;
+use serde::Serialize;
+use tera::{Tera, Value};
+
+use crate::{
+    client::Client,
+    config::Config,
+    date::DateString,
+    latex::{Asset, Document, Latex},
+    money::Money,
+    project::Project,
+    storage::get_templates_dir,
+};
+
+pub struct TemplateSet {
+    tera: Tera,
+    assets: Vec<Asset>,
+}
+
+impl TemplateSet {
+    /// The template set selected by the project, the client or else the config, if any.
+    pub fn select(
+        project: &Project,
+        client: &Client,
+        config: &Config,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(name) = project
+            .template
+            .as_ref()
+            .or(client.template.as_ref())
+            .or(config.templates.default.as_ref())
+        else {
+            return Ok(None);
+        };
+
+        let templates_dir = get_templates_dir(config).context("getting templates directory")?;
+        let dir = templates_dir.join(name);
+        if !dir.is_dir() {
+            bail!("template set {:?} not found at {:?}", name, dir);
+        }
+
+        let template_set =
+            Self::load(&dir).with_context(|| format!("loading template set {:?}", name))?;
+
+        Ok(Some(template_set))
+    }
+
+    pub fn load(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut tera = Tera::default();
+        tera.autoescape_on(vec![".tex"]);
+        tera.set_escape_fn(escape_latex);
+        tera.register_filter("money", money_filter);
+        tera.register_filter("long_date", long_date_filter);
+
+        let mut assets = Vec::new();
+        let entries = std::fs::read_dir(dir.as_ref()).context("reading template directory")?;
+        for entry in entries {
+            let path = entry.context("reading template directory entry")?.path();
+            let Some(filename) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !path.is_file() {
+                continue;
+            }
+
+            if filename.ends_with(".tex") {
+                tera.add_template_file(&path, Some(filename))
+                    .with_context(|| format!("parsing template {:?}", path))?;
+            } else {
+                let data =
+                    std::fs::read(&path).with_context(|| format!("reading asset {:?}", path))?;
+                assets.push(Asset {
+                    data,
+                    filename: filename.to_owned(),
+                });
+            }
+        }
+
+        Ok(Self { tera, assets })
+    }
+
+    /// Render the template `name`, or return `None` if the set doesn't have it.
+    pub fn render(&self, name: &str, context: &impl Serialize) -> anyhow::Result<Option<String>> {
+        if !self
+            .tera
+            .get_template_names()
+            .any(|template| template == name)
+        {
+            return Ok(None);
+        }
+
+        let context =
+            tera::Context::from_serialize(context).context("serializing template context")?;
+        let rendered = self
+            .tera
+            .render(name, &context)
+            .with_context(|| format!("rendering template {:?}", name))?;
+
+        Ok(Some(rendered))
+    }
+
+    /// The given assets with this set's added, replacing those with the same filename.
+    pub fn with_assets(&self, mut assets: Vec<Asset>) -> Vec<Asset> {
+        assets.retain(|asset| {
+            !self
+                .assets
+                .iter()
+                .any(|own_asset| own_asset.filename == asset.filename)
+        });
+        assets.extend(self.assets.iter().cloned());
+
+        assets
+    }
+}
+
+/// The selected template set, if any, and the assets for compiling a document:
+/// `builtin` and the branding images, replaced by the set's files of the same name.
+fn select_assets<T, C>(
+    builtin: Vec<Asset>,
+    document: &Document<'_, T, C>,
+    config: &Config,
+) -> anyhow::Result<(Option<TemplateSet>, Vec<Asset>)> {
+    let mut assets = builtin;
+    assets.extend(
+        document
+            .me
+            .branding
+            .assets()
+            .context("loading branding images")?,
+    );
+
+    let template_set = TemplateSet::select(document.project, document.client, config)
+        .context("selecting template set")?;
+    if let Some(template_set) = &template_set {
+        assets = template_set.with_assets(assets);
+    }
+
+    Ok((template_set, assets))
+}
+
+/// Render a document's LaTeX with the selected template set if it has
+/// its template, or else with the built-in template.
+/// Returns the LaTeX and the assets needed to compile it,
+/// including the logo and signature.
+pub fn render_latex<T: askama::Template, C: Serialize>(
+    document: &Document<'_, T, C>,
+    config: &Config,
+) -> anyhow::Result<(String, Vec<Asset>)> {
+    let (template_set, assets) = select_assets(vec![Asset::invoice_class()], document, config)?;
+    if let Some(template_set) = template_set {
+        let name = format!("{}.tex", document.name);
+        if let Some(tex) = template_set.render(&name, document.context)? {
+            return Ok((tex, assets));
+        }
+    }
+
+    let tex = document
+        .latex
+        .render()
+        .context("rendering built-in template")?;
+
+    Ok((tex, assets))
+}
+
+/// The assets needed to compile a document with Typst: the built-in Typst templates
+/// or those of the selected template set, the logo and signature, and the document's
+/// data as `data.json`.
+pub fn typst_assets<T, C: Serialize>(
+    document: &Document<'_, T, C>,
+    config: &Config,
+) -> anyhow::Result<Vec<Asset>> {
+    let (_, mut assets) = select_assets(Asset::typst_templates(), document, config)?;
+    let main = format!("{}.typ", document.name);
+    if !assets.iter().any(|asset| asset.filename == main) {
+        bail!("there's no Typst template {:?}", main);
+    }
+    // Unlike LaTeX, Typst can't reliably include PDF images, so fail rather than leave them out
+    if let Some(image) = assets
+        .iter()
+        .find(|asset| matches!(asset.filename.as_str(), "logo.pdf" | "signature.pdf"))
+    {
+        bail!(
+            "{} is a PDF, which the Typst renderer can't include, use a PNG or JPEG image instead",
+            image.filename
+        );
+    }
+
+    let data = serde_json::to_vec_pretty(document.context).context("serializing document data")?;
+    assets.push(Asset {
+        data,
+        filename: "data.json".to_owned(),
+    });
+
+    Ok(assets)
+}
+
+fn escape_latex(input: &str) -> String {
+    let mut escaped = String::new();
+    // Writing to a string can't fail
+    _ = Latex.write_escaped(&mut escaped, input);
+
+    escaped
+}
+
+/// Format an amount with two decimal places, e.g. `{{ balance | money }}`.
+fn money_filter(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let amount = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(string) => string.clone(),
+        _ => {
+            return Err(tera::Error::msg(format!(
+                "money filter expects an amount, found {}",
+                value
+            )))
+        }
+    };
+    let money = Money::from_str(&amount)
+        .map_err(|err| tera::Error::msg(format!("invalid amount {:?}: {}", amount, err)))?;
+
+    Ok(Value::String(money.to_string()))
+}
+
+/// Format a date as e.g. `January 7, 2023`, like the built-in templates.
+fn long_date_filter(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let Value::String(string) = value else {
+        return Err(tera::Error::msg(format!(
+            "long_date filter expects a date, found {}",
+            value
+        )));
+    };
+    let date = DateString::try_new(string.clone())
+        .map_err(|err| tera::Error::msg(format!("invalid date {:?}: {}", string, err)))?;
+
+    Ok(Value::String(date.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{Config, StorageConfig},
+        latex::{Asset, Document},
+        test_util::create_full_test_invoice,
+    };
+
+    use super::{typst_assets, TemplateSet};
+
+    #[test]
+    fn test_render_template_set() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("invoice.tex"),
+            "{{ project.name }}: {{ invoice.date | long_date }}, {{ totals.total | money }}",
+        )?;
+        std::fs::write(dir.path().join("CSMinimalInvoice.cls"), "% custom class")?;
+        std::fs::write(dir.path().join("logo.png"), [0x89, b'P', b'N', b'G'])?;
+
+        let template_set = TemplateSet::load(dir.path())?;
+
+        let full_invoice = create_full_test_invoice();
+        let context = full_invoice.template_context();
+        assert_eq!(
+            template_set.render("invoice.tex", &context)?.as_deref(),
+            Some(r"Test Project \#1: January 7, 2023, 29.50")
+        );
+        assert_eq!(template_set.render("receipt.tex", &context)?, None);
+
+        let assets = template_set.with_assets(vec![Asset::invoice_class()]);
+        let mut filenames: Vec<&str> = assets.iter().map(|a| a.filename.as_str()).collect();
+        filenames.sort();
+        assert_eq!(filenames, ["CSMinimalInvoice.cls", "logo.png"]);
+        assert_eq!(
+            assets
+                .iter()
+                .find(|a| a.filename == "CSMinimalInvoice.cls")
+                .map(|a| a.data.as_slice()),
+            Some(b"% custom class".as_slice())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_typst_assets() -> anyhow::Result<()> {
+        let full_invoice = create_full_test_invoice();
+        let config = Config {
+            me: full_invoice.me.clone(),
+            storage: StorageConfig { data_dir: None },
+            invoice: Default::default(),
+            beancount: Default::default(),
+            templates: Default::default(),
+            pdf: Default::default(),
+        };
+        let context = full_invoice.template_context();
+        let document = Document {
+            name: "invoice",
+            latex: &full_invoice,
+            context: &context,
+            me: &full_invoice.me,
+            project: &full_invoice.project,
+            client: &full_invoice.client,
+        };
+
+        let assets = typst_assets(&document, &config)?;
+        let mut filenames: Vec<&str> = assets.iter().map(|a| a.filename.as_str()).collect();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            [
+                "credit_note.typ",
+                "data.json",
+                "invoice.typ",
+                "pdoc.typ",
+                "quote.typ",
+                "receipt.typ"
+            ]
+        );
+
+        let data = assets
+            .iter()
+            .find(|a| a.filename == "data.json")
+            .map(|a| serde_json::from_slice::<serde_json::Value>(&a.data))
+            .transpose()?
+            .expect("data.json asset");
+        assert_eq!(data["invoice"]["number"], 17);
+        assert_eq!(data["project"]["name"], "Test Project #1");
+        assert_eq!(data["totals"]["total"], "29.50");
+        assert_eq!(data["formatted"]["items"][0]["unit_price"], "10.30");
+        assert_eq!(data["formatted"]["items"][1]["amount"], "19.20");
+        assert_eq!(data["formatted"]["total"], "29.50");
+
+        let statement = Document {
+            name: "statement",
+            ..document
+        };
+        assert!(typst_assets(&statement, &config).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Fixtures shared by the tests of several modules.
+
+use std::str::FromStr;
+
+use crate::{
+    address::MailingAddress,
+    client::Client,
+    contact::ContactInfo,
+    date::DateString,
+    invoice::{FullInvoice, Invoice, LineItem},
+    me::{Branding, Me, PaymentMethod},
+    money::{Currency, Money, Quantity},
+    project::Project,
+};
+
+/// An untaxed invoice 17 for 29.50 USD with two line items,
+/// together with the freelancer, project and client it belongs to.
+pub(crate) fn create_full_test_invoice() -> FullInvoice {
+    FullInvoice {
+        me: Me {
+            name: "Test User".to_owned(),
+            address: MailingAddress {
+                addr1: "123 Test Street".to_owned(),
+                addr2: None,
+                addr3: None,
+                city: "Twin Falls".to_owned(),
+                state: "Idaho".to_owned(),
+                zip: "12345".to_owned(),
+            },
+            contact: ContactInfo {
+                email: "test@example.com".to_owned(),
+                phone: "(123) 456-7890".to_owned(),
+            },
+            payment: [PaymentMethod {
+                name: "PayPal".to_owned(),
+                display_text: None,
+                url: None,
+                account: None,
+            }]
+            .to_vec(),
+            branding: Branding::default(),
+        },
+        invoice: Invoice {
+            number: 17,
+            project_ref: "Test Project #1".to_owned().into(),
+            date: DateString::try_new("2023-01-07".to_owned()).unwrap(),
+            due_date: DateString::try_new("2023-01-21".to_owned()).unwrap(),
+            currency: Currency::default(),
+            tax_rate: None,
+            items: [
+                LineItem {
+                    description: "Test the first thing".to_owned(),
+                    quantity: Quantity::from_str("1").unwrap(),
+                    unit_price: Money::from_str("10.3").unwrap(),
+                    tax_rate: None,
+                    discount: None,
+                },
+                LineItem {
+                    description: "Test the second thing".to_owned(),
+                    quantity: Quantity::from_str("2").unwrap(),
+                    unit_price: Money::from_str("9.6").unwrap(),
+                    tax_rate: None,
+                    discount: None,
+                },
+            ]
+            .to_vec(),
+            expenses: Vec::new(),
+            discounts: Vec::new(),
+            conditions: None,
+        },
+        project: Project {
+            name: "Test Project #1".to_owned().into(),
+            description: "A great project for testing".to_owned(),
+            client_ref: "Test Client #1".to_owned().into(),
+            currency: None,
+            hourly_rate: None,
+            days_to_pay: None,
+            conditions: None,
+            template: None,
+        },
+        client: Client {
+            name: "Test Client #1".to_owned().into(),
+            address: MailingAddress {
+                addr1: "124 Test Avenue".to_owned(),
+                addr2: None,
+                addr3: None,
+                city: "New York".to_owned(),
+                state: "New York".to_owned(),
+                zip: "54321".to_owned(),
+            },
+            contact: ContactInfo {
+                email: "client@example.com".to_owned(),
+                phone: "(321) 654-0987".to_owned(),
+            },
+            currency: None,
+            tax_rate: None,
+            hourly_rate: None,
+            days_to_pay: None,
+            conditions: None,
+            template: None,
+        },
+    }
+}
@@ -0,0 +1,416 @@
+//! Plain-text and Markdown renderings of invoices,
+//! for pasting into emails, tickets and chat.
+
+use crate::{
+    address::MailingAddress,
+    contact::ContactInfo,
+    invoice::{FullInvoice, LineItem},
+    me::PaymentMethod,
+};
+
+/// Whether a document is written as plain text or as Markdown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextStyle {
+    Plain,
+    Markdown,
+}
+
+const HEADERS: [&str; 4] = ["Description", "Quantity", "Unit Price", "Amount"];
+
+/// A row of the items table.
+enum Row {
+    Item([String; 4]),
+    /// A heading for the items below it, e.g. expenses.
+    Section(String),
+    /// An amount below the items, such as the subtotal or a tax.
+    Summary {
+        label: String,
+        amount: String,
+        strong: bool,
+    },
+}
+
+impl Row {
+    fn item(item: &LineItem) -> Self {
+        Self::Item([
+            item.description.clone(),
+            item.quantity.to_string(),
+            item.unit_price.to_string(),
+            item.subtotal().to_string(),
+        ])
+    }
+
+    fn summary(label: String, amount: String) -> Self {
+        Self::Summary {
+            label,
+            amount,
+            strong: false,
+        }
+    }
+}
+
+impl TextStyle {
+    fn heading(&self, text: &str) -> String {
+        match self {
+            Self::Plain => format!("{}\n{}", text, "=".repeat(text.chars().count())),
+            Self::Markdown => format!("# {}", escape_markdown(text)),
+        }
+    }
+
+    fn subheading(&self, text: &str) -> String {
+        match self {
+            Self::Plain => format!("{}\n{}", text, "-".repeat(text.chars().count())),
+            Self::Markdown => format!("## {}", escape_markdown(text)),
+        }
+    }
+
+    /// Labelled values, one per line.
+    fn fields(&self, fields: &[(&str, String)]) -> String {
+        let label_width = fields
+            .iter()
+            .map(|(label, _)| label.chars().count() + 1)
+            .max()
+            .unwrap_or(0);
+
+        let lines: Vec<String> = fields
+            .iter()
+            .map(|(label, value)| match self {
+                Self::Plain => format!("{:<label_width$} {}", format!("{}:", label), value),
+                Self::Markdown => format!("- **{}:** {}", label, escape_markdown(value)),
+            })
+            .collect();
+
+        lines.join("\n")
+    }
+
+    /// Lines which are kept apart, such as an address.
+    fn lines(&self, lines: &[String]) -> String {
+        match self {
+            Self::Plain => lines.join("\n"),
+            // A trailing backslash is a hard line break
+            Self::Markdown => lines
+                .iter()
+                .map(|line| escape_markdown(line))
+                .collect::<Vec<_>>()
+                .join("\\\n"),
+        }
+    }
+
+    fn payment_methods(&self, methods: &[PaymentMethod]) -> String {
+        let lines: Vec<String> = methods
+            .iter()
+            .map(|method| match (self, &method.url) {
+                (Self::Plain, Some(url)) => format!("- {}: {}", method, url),
+                (Self::Plain, None) => format!("- {}", method),
+                (Self::Markdown, Some(url)) => {
+                    format!("- [{}]({})", escape_markdown(&method.to_string()), url)
+                }
+                (Self::Markdown, None) => format!("- {}", escape_markdown(&method.to_string())),
+            })
+            .collect();
+
+        lines.join("\n")
+    }
+
+    fn table(&self, rows: &[Row]) -> String {
+        match self {
+            Self::Plain => plain_table(rows),
+            Self::Markdown => markdown_table(rows),
+        }
+    }
+}
+
+/// A table with aligned columns, with the amounts below the items
+/// labelled across the first three columns.
+fn plain_table(rows: &[Row]) -> String {
+    let mut widths = HEADERS.map(str::len);
+    let mut label_width = 0;
+    for row in rows {
+        match row {
+            Row::Item(cells) => {
+                for (width, cell) in widths.iter_mut().zip(cells) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            Row::Section(title) => widths[0] = widths[0].max(title.chars().count()),
+            Row::Summary { label, amount, .. } => {
+                label_width = label_width.max(label.chars().count());
+                widths[3] = widths[3].max(amount.chars().count());
+            }
+        }
+    }
+
+    let span = widths[0] + widths[1] + widths[2] + 4;
+    if label_width > span {
+        widths[0] += label_width - span;
+    }
+    let span = span.max(label_width);
+    let [w0, w1, w2, w3] = widths;
+    let rule = "-".repeat(span + 2 + w3);
+
+    let mut lines = vec![
+        format!(
+            "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+            HEADERS[0], HEADERS[1], HEADERS[2], HEADERS[3]
+        ),
+        rule.clone(),
+    ];
+    let mut in_summary = false;
+    for row in rows {
+        match row {
+            Row::Item([description, quantity, unit_price, amount]) => lines.push(format!(
+                "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+                description, quantity, unit_price, amount
+            )),
+            Row::Section(title) => lines.push(title.clone()),
+            Row::Summary { label, amount, .. } => {
+                if !in_summary {
+                    lines.push(rule.clone());
+                    in_summary = true;
+                }
+                lines.push(format!("{:>span$}  {:>w3$}", label, amount));
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// A Markdown table, padded so that it also reads well unrendered.
+fn markdown_table(rows: &[Row]) -> String {
+    let bold = |text: &str| format!("**{}**", text);
+
+    let mut cells = vec![HEADERS.map(str::to_owned)];
+    for row in rows {
+        cells.push(match row {
+            Row::Item(item) => item.clone().map(|cell| escape_markdown(&cell)),
+            Row::Section(title) => [
+                bold(&escape_markdown(title)),
+                String::new(),
+                String::new(),
+                String::new(),
+            ],
+            Row::Summary {
+                label,
+                amount,
+                strong: false,
+            } => [
+                escape_markdown(label),
+                String::new(),
+                String::new(),
+                amount.clone(),
+            ],
+            Row::Summary {
+                label,
+                amount,
+                strong: true,
+            } => [
+                bold(&escape_markdown(label)),
+                String::new(),
+                String::new(),
+                bold(amount),
+            ],
+        });
+    }
+
+    // Delimiter rows need at least three characters per column
+    let mut widths = [3; 4];
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let [w0, w1, w2, w3] = widths;
+
+    let mut lines = Vec::new();
+    for (i, [description, quantity, unit_price, amount]) in cells.iter().enumerate() {
+        lines.push(format!(
+            "| {:<w0$} | {:>w1$} | {:>w2$} | {:>w3$} |",
+            description, quantity, unit_price, amount
+        ));
+        if i == 0 {
+            lines.push(format!(
+                "| {} | {}: | {}: | {}: |",
+                "-".repeat(w0),
+                "-".repeat(w1 - 1),
+                "-".repeat(w2 - 1),
+                "-".repeat(w3 - 1)
+            ));
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// A name with its address, email and phone number, one per line.
+fn party_lines(name: &str, address: &MailingAddress, contact: &ContactInfo) -> Vec<String> {
+    let mut lines = vec![name.to_owned(), address.addr1.clone()];
+    lines.extend(address.addr2.clone());
+    lines.extend(address.addr3.clone());
+    lines.push(format!(
+        "{}, {} {}",
+        address.city, address.state, address.zip
+    ));
+    lines.push(contact.email.clone());
+    lines.push(contact.phone.clone());
+    lines.retain(|line| !line.is_empty());
+
+    lines
+}
+
+/// Render an invoice with the same totals as its PDF.
+pub fn render_invoice(full_invoice: &FullInvoice, style: TextStyle) -> String {
+    let FullInvoice {
+        me,
+        invoice,
+        project,
+        client,
+    } = full_invoice;
+    let totals = invoice.totals();
+
+    let mut rows: Vec<Row> = invoice.items.iter().map(Row::item).collect();
+    if !invoice.expenses.is_empty() {
+        rows.push(Row::Section("Expenses".to_owned()));
+        rows.extend(invoice.expenses.iter().map(Row::item));
+    }
+    rows.push(Row::summary(
+        "Subtotal".to_owned(),
+        totals.subtotal.to_string(),
+    ));
+    for discount in &totals.discounts {
+        rows.push(Row::summary(
+            format!("Discount: {}", discount.description),
+            format!("-{}", discount.amount),
+        ));
+    }
+    for tax in &totals.taxes {
+        rows.push(Row::summary(
+            format!("Tax {}%", tax.rate),
+            tax.tax.to_string(),
+        ));
+    }
+    rows.push(Row::Summary {
+        label: format!("Total ({})", invoice.currency),
+        amount: totals.total.to_string(),
+        strong: true,
+    });
+
+    let mut sections = vec![
+        style.heading(&format!("Invoice #{}", invoice.number)),
+        style.fields(&[
+            ("Date", invoice.date.to_string()),
+            ("Due", invoice.due_date.to_string()),
+            ("Project", project.name.to_string()),
+            ("Description", project.description.clone()),
+        ]),
+        style.subheading("Bill to"),
+        style.lines(&party_lines(
+            &client.name.to_string(),
+            &client.address,
+            &client.contact,
+        )),
+        style.table(&rows),
+    ];
+    if let Some(conditions) = &invoice.conditions {
+        sections.push(match style {
+            TextStyle::Plain => conditions.clone(),
+            TextStyle::Markdown => escape_markdown(conditions),
+        });
+    }
+    sections.push(style.subheading("Pay to"));
+    sections.push(style.lines(&party_lines(&me.name, &me.address, &me.contact)));
+    if !me.payment.is_empty() {
+        sections.push(style.subheading("Payment methods"));
+        sections.push(style.payment_methods(&me.payment));
+    }
+
+    let mut rendered = sections.join("\n\n");
+    rendered.push('\n');
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::create_full_test_invoice;
+
+    use super::{render_invoice, TextStyle};
+
+    #[test]
+    fn test_render_invoice_text() {
+        let full_invoice = create_full_test_invoice();
+
+        let expected = "\
+Invoice #17
+===========
+
+Date:        January 7, 2023
+Due:         January 21, 2023
+Project:     Test Project #1
+Description: A great project for testing
+
+Bill to
+-------
+
+Test Client #1
+124 Test Avenue
+New York, New York 54321
+client@example.com
+(321) 654-0987
+
+Description            Quantity  Unit Price  Amount
+---------------------------------------------------
+Test the first thing          1       10.30   10.30
+Test the second thing         2        9.60   19.20
+---------------------------------------------------
+                                   Subtotal   29.50
+                                Total (USD)   29.50
+
+Pay to
+------
+
+Test User
+123 Test Street
+Twin Falls, Idaho 12345
+test@example.com
+(123) 456-7890
+
+Payment methods
+---------------
+
+- PayPal
+";
+
+        assert_eq!(render_invoice(&full_invoice, TextStyle::Plain), expected);
+    }
+
+    #[test]
+    fn test_render_invoice_markdown() {
+        let full_invoice = create_full_test_invoice();
+
+        let markdown = render_invoice(&full_invoice, TextStyle::Markdown);
+
+        assert!(markdown.starts_with("# Invoice #17\n\n- **Date:** January 7, 2023\n"));
+        assert!(markdown.contains("Test Client #1\\\n124 Test Avenue\\\n"));
+        assert!(markdown.contains(
+            "| Description           | Quantity | Unit Price |    Amount |\n\
+             | --------------------- | -------: | ---------: | --------: |\n\
+             | Test the first thing  |        1 |      10.30 |     10.30 |\n"
+        ));
+        assert!(
+            markdown.contains("| **Total (USD)**       |          |            | **29.50** |\n")
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use std::{fs::File, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    config::Config,
+    date::DateString,
+    id::Id,
+    invoice::LineItem,
+    money::{Money, Quantity},
+    storage::get_timelogs_dir,
+};
+
+#[derive(Clone, Debug, Error)]
+pub enum HoursError {
+    #[error("Duration {0:?} must be given in hours (e.g. 1.5) or as e.g. 1h30m, 2h or 45m")]
+    Invalid(String),
+    #[error("Duration {0:?} must be positive")]
+    NotPositive(String),
+}
+
+/// A duration in hours, parsed from e.g. `1.5`, `1h30m`, `2h` or `45m`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hours(pub Quantity);
+
+impl FromStr for Hours {
+    type Err = HoursError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || HoursError::Invalid(s.to_owned());
+        let trimmed = s.trim();
+
+        let total = match Decimal::from_str(trimmed) {
+            Ok(hours) => hours,
+            Err(_) => Self::parse_hours_and_minutes(trimmed).ok_or_else(invalid)?,
+        };
+
+        if total <= Decimal::ZERO {
+            return Err(HoursError::NotPositive(s.to_owned()));
+        }
+
+        Ok(Self(Quantity::new(total)))
+    }
+}
+
+impl Hours {
+    /// Parse e.g. `1h30m`, `2h` or `45m`, rounded to two decimals.
+    fn parse_hours_and_minutes(s: &str) -> Option<Decimal> {
+        let (hours, rest) = match s.split_once('h') {
+            Some((hours, rest)) => (hours, rest),
+            None => ("0", s),
+        };
+        let minutes = match rest {
+            "" => "0",
+            rest => rest.strip_suffix('m')?,
+        };
+
+        let hours = Decimal::from_str(hours).ok()?;
+        let minutes = Decimal::from_str(minutes).ok()?;
+
+        Some((hours + minutes / Decimal::from(60)).round_dp(2))
+    }
+}
+
+/// Time spent on a project.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeEntry {
+    pub date: DateString,
+    /// Duration in hours.
+    pub hours: Quantity,
+    pub description: String,
+    #[serde(default = "default_billable")]
+    pub billable: bool,
+    /// Hourly rate for this entry, overriding the rate given when invoicing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<Money>,
+    /// The invoice this entry was billed on, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoice_num: Option<u32>,
+}
+
+fn default_billable() -> bool {
+    true
+}
+
+impl TimeEntry {
+    pub fn is_unbilled(&self) -> bool {
+        self.billable && self.invoice_num.is_none()
+    }
+}
+
+/// A timer started with `pdoc time start`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunningTimer {
+    /// RFC 3339 timestamp.
+    pub started_at: String,
+    pub description: String,
+}
+
+fn now() -> OffsetDateTime {
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+/// All time entries of a project, stored as `timelogs/<project>.yaml`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeLog {
+    #[serde(default)]
+    pub entries: Vec<TimeEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running: Option<RunningTimer>,
+}
+
+impl TimeLog {
+    fn path(project: &Id, config: &Config) -> anyhow::Result<PathBuf> {
+        let timelogs_dir = get_timelogs_dir(config).context("getting timelogs directory")?;
+
+        Ok(timelogs_dir.join(format!("{}.yaml", project)))
+    }
+
+    /// Load a project's time log, which is empty if no time was logged yet.
+    pub fn load(project: &Id, config: &Config) -> anyhow::Result<Self> {
+        let path = Self::path(project, config)?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let file = File::open(path).context("opening time log file")?;
+        let log: TimeLog = serde_yaml::from_reader(file).context("parsing time log yaml")?;
+
+        Ok(log)
+    }
+
+    pub fn save(&self, project: &Id, config: &Config) -> anyhow::Result<()> {
+        let path = Self::path(project, config)?;
+        let file = File::create(path).context("creating time log file")?;
+
+        serde_yaml::to_writer(file, self).context("serializing time log yaml")?;
+
+        Ok(())
+    }
+
+    /// Move a project's time log to a new project name, if it has one.
+    pub fn rename(old: &Id, new: &Id, config: &Config) -> anyhow::Result<()> {
+        let old_path = Self::path(old, config)?;
+
+        if old_path.exists() {
+            let new_path = Self::path(new, config)?;
+            std::fs::rename(old_path, new_path).context("renaming time log file")?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete a project's time log, if it has one.
+    pub fn delete(project: &Id, config: &Config) -> anyhow::Result<()> {
+        let path = Self::path(project, config)?;
+
+        if path.exists() {
+            std::fs::remove_file(path).context("removing time log file")?;
+        }
+
+        Ok(())
+    }
+
+    /// Names of all projects with a time log.
+    pub fn list(config: &Config) -> anyhow::Result<Vec<Id>> {
+        let timelogs_dir = get_timelogs_dir(config).context("getting timelogs directory")?;
+
+        let projects: Vec<Id> = timelogs_dir
+            .read_dir()
+            .context("listing time log files")?
+            .filter_map(|entry_res| {
+                let entry = entry_res.ok()?;
+                let path = entry.path();
+                let stem = path.file_stem()?.to_string_lossy();
+
+                Some(stem.into_owned().into())
+            })
+            .collect();
+
+        Ok(projects)
+    }
+
+    pub fn start(&mut self, description: String) -> anyhow::Result<()> {
+        if let Some(running) = &self.running {
+            bail!(
+                "a timer for {:?} is already running since {}",
+                running.description,
+                running.started_at
+            );
+        }
+
+        let started_at = now().format(&Rfc3339).context("formatting start time")?;
+        self.running = Some(RunningTimer {
+            started_at,
+            description,
+        });
+
+        Ok(())
+    }
+
+    /// Stop the running timer and log the elapsed time, rounded to the minute.
+    /// The timer keeps running if less than a minute has passed.
+    pub fn stop(&mut self) -> anyhow::Result<&TimeEntry> {
+        let running = self.running.take().ok_or(anyhow!("no timer is running"))?;
+        let started_at = OffsetDateTime::parse(&running.started_at, &Rfc3339)
+            .context("parsing timer start time")?;
+
+        let minutes = (now() - started_at).whole_minutes();
+        if minutes <= 0 {
+            let started_at = running.started_at.clone();
+            self.running = Some(running);
+            bail!(
+                "the timer started at {} has run for less than a minute",
+                started_at
+            );
+        }
+        let hours = (Decimal::from(minutes) / Decimal::from(60)).round_dp(2);
+
+        self.entries.push(TimeEntry {
+            date: started_at.date().into(),
+            hours: Quantity::new(hours),
+            description: running.description,
+            billable: true,
+            rate: None,
+            invoice_num: None,
+        });
+
+        Ok(self.entries.last().expect("entry was just pushed"))
+    }
+
+    pub fn unbilled(&self) -> impl Iterator<Item = &TimeEntry> {
+        self.entries.iter().filter(|entry| entry.is_unbilled())
+    }
+
+    /// Line items for all unbilled entries, charged at their own rate or `default_rate`,
+    /// each paired with the entry it bills.
+    pub fn unbilled_items(
+        &self,
+        default_rate: Option<Money>,
+    ) -> anyhow::Result<Vec<(TimeEntry, LineItem)>> {
+        self.unbilled()
+            .map(|entry| {
+                let unit_price = entry.rate.or(default_rate).ok_or(anyhow!(
+                    "time entry {:?} on {} has no hourly rate",
+                    entry.description,
+                    entry.date
+                ))?;
+
+                let item = LineItem {
+                    description: format!("{} ({})", entry.description, entry.date),
+                    quantity: entry.hours,
+                    unit_price,
+                    tax_rate: None,
+                    discount: None,
+                };
+
+                Ok((entry.clone(), item))
+            })
+            .collect()
+    }
+
+    /// Mark the given entries as billed on the given invoice, unless they were
+    /// billed in the meantime. Entries are matched by their contents rather than
+    /// their position, since the log may have changed since they were billed.
+    pub fn mark_billed(&mut self, entries: &[TimeEntry], invoice_num: u32) {
+        for billed in entries {
+            let unbilled = self
+                .entries
+                .iter_mut()
+                .find(|entry| entry.is_unbilled() && **entry == *billed);
+            if let Some(entry) = unbilled {
+                entry.invoice_num = Some(invoice_num);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        date::DateString,
+        money::{Money, Quantity},
+    };
+
+    use super::{Hours, TimeEntry, TimeLog};
+
+    fn entry(hours: &str, billable: bool, rate: Option<&str>) -> TimeEntry {
+        TimeEntry {
+            date: DateString::try_new("2023-01-05".to_owned()).unwrap(),
+            hours: Quantity::from_str(hours).unwrap(),
+            description: "Development".to_owned(),
+            billable,
+            rate: rate.map(|rate| Money::from_str(rate).unwrap()),
+            invoice_num: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_hours() -> anyhow::Result<()> {
+        assert_eq!(Hours::from_str("1.5")?.0, Quantity::from_str("1.5")?);
+        assert_eq!(Hours::from_str("1h30m")?.0, Quantity::from_str("1.5")?);
+        assert_eq!(Hours::from_str("2h")?.0, Quantity::from_str("2")?);
+        assert_eq!(Hours::from_str("45m")?.0, Quantity::from_str("0.75")?);
+        assert_eq!(Hours::from_str("20m")?.0, Quantity::from_str("0.33")?);
+        assert!(Hours::from_str("1h30").is_err());
+        assert!(Hours::from_str("soon").is_err());
+        assert!(Hours::from_str("0").is_err());
+        assert!(Hours::from_str("-1.5").is_err());
+        assert!(Hours::from_str("0h0m").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bill_unbilled_entries() -> anyhow::Result<()> {
+        let mut log = TimeLog {
+            entries: [
+                entry("2", true, None),
+                entry("1", false, None),
+                entry("0.5", true, Some("120")),
+            ]
+            .to_vec(),
+            running: None,
+        };
+
+        let (billed, items): (Vec<_>, Vec<_>) = log
+            .unbilled_items(Some(Money::from_str("80")?))?
+            .into_iter()
+            .unzip();
+        assert_eq!(billed, [log.entries[0].clone(), log.entries[2].clone()]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].description, "Development (January 5, 2023)");
+        assert_eq!(items[0].unit_price, Money::from_str("80")?);
+        assert_eq!(items[1].unit_price, Money::from_str("120")?);
+
+        assert!(log.unbilled_items(None).is_err());
+
+        // Entries logged or removed after the invoice was created don't shift
+        // which entries are marked
+        log.entries.remove(1);
+        log.entries.insert(0, entry("1.5", true, None));
+        log.mark_billed(&billed, 17);
+        assert_eq!(log.unbilled().count(), 1);
+        assert_eq!(log.entries[0].invoice_num, None);
+        assert_eq!(log.entries[1].invoice_num, Some(17));
+        assert_eq!(log.entries[2].invoice_num, Some(17));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_before_a_minute() -> anyhow::Result<()> {
+        let mut log = TimeLog::default();
+        log.start("Development".to_owned())?;
+
+        assert!(log.stop().is_err());
+        assert!(log.running.is_some());
+        assert!(log.entries.is_empty());
+
+        Ok(())
+    }
+}